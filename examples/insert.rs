@@ -4,20 +4,21 @@ use std::sync::Arc;
 // Simple test resource
 struct ResourrceA(u32);
 
-// This system will set the value of Option<ResourrceA>. basically acting as adding it to the world at runtime
+// This system will queue the insertion of ResourrceA. It only gets added to the world once the dispatch finishes
 fn system_a(w: &World) {
-    w.get_mut::<Option<ResourrceA>>()
-        .unwrap()
-        .replace(ResourrceA(0));
+    if !w.contains::<ResourrceA>() {
+        w.commands().insert(ResourrceA(0));
+    }
 }
 
-// This system executes after system "a" and will read from the now initialized resource
+// This system executes after system "a" and will read from the resource once it was inserted
 fn system_b(w: &World) {
-    let res = w
-        .get::<Option<ResourrceA>>()
-        .unwrap()
-        .map(|x| x.as_ref().unwrap());
-    dbg!(res.0);
+    match w.get::<ResourrceA>() {
+        Ok(res) => {
+            dbg!(res.0);
+        }
+        Err(_) => log::info!("ResourrceA is not present yet"),
+    }
 }
 
 fn main() {
@@ -27,21 +28,19 @@ fn main() {
 
     // Create a registry and add the two systems (making sure to set the proper accesses)
     let mut registry = Registry::default();
-    registry
-        .insert(system_a)
-        .unwrap()
-        .writes::<Option<ResourrceA>>();
+    registry.insert(system_a).unwrap();
     registry
         .insert(system_b)
         .unwrap()
         .after(system_a)
-        .reads::<Option<ResourrceA>>();
-
-    // Create a test world and add the resource
-    let mut world = World::default();
-    world.insert(Option::<ResourrceA>::None);
+        .reads::<ResourrceA>();
 
-    // Create a dispatcher by sorting the registry and execute it
-    let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
-    dispatcher.dispatch();
+    // Create a dispatcher by sorting the registry and execute it twice
+    // The first dispatch inserts the resource, and the second one can read it
+    let mut dispatcher = registry
+        .sort()
+        .unwrap()
+        .build(Arc::new(World::default()), None);
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
 }
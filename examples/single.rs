@@ -25,5 +25,5 @@ fn main() {
 
     // Create a dispatcher by sorting the registry and execute it
     let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
-    dispatcher.dispatch();
+    dispatcher.dispatch().unwrap();
 }
@@ -23,5 +23,5 @@ fn main() {
     let mut world = World::default();
     world.insert(123u32);
     let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
-    dispatcher.dispatch();
+    dispatcher.dispatch().unwrap();
 }
@@ -0,0 +1,126 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use crate::StageId;
+
+// A single get/get_mut call that a system attempted while the dispatcher was auditing
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AccessRecord {
+    pub name: &'static str,
+    pub mutable: bool,
+}
+
+// Declared accesses of a system compared to the accesses it actually attempted
+#[derive(Clone, Debug)]
+pub struct SystemAudit {
+    pub stage: StageId,
+    pub declared_reads: BTreeSet<&'static str>,
+    pub declared_writes: BTreeSet<&'static str>,
+    pub reads: BTreeSet<&'static str>,
+    pub writes: BTreeSet<&'static str>,
+}
+
+impl SystemAudit {
+    pub(crate) fn new(
+        stage: StageId,
+        declared_reads: Vec<&'static str>,
+        declared_writes: Vec<&'static str>,
+    ) -> Self {
+        Self {
+            stage,
+            declared_reads: declared_reads.into_iter().collect(),
+            declared_writes: declared_writes.into_iter().collect(),
+            reads: BTreeSet::default(),
+            writes: BTreeSet::default(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, records: impl IntoIterator<Item = AccessRecord>) {
+        for AccessRecord { name, mutable } in records {
+            if mutable {
+                self.writes.insert(name);
+            } else {
+                self.reads.insert(name);
+            }
+        }
+    }
+
+    // Resources the system read without declaring them (these accesses were denied)
+    pub fn under_declared_reads(&self) -> Vec<&'static str> {
+        self.reads
+            .difference(&self.declared_reads)
+            .copied()
+            .collect()
+    }
+
+    // Resources the system wrote to without declaring them (these accesses were denied)
+    pub fn under_declared_writes(&self) -> Vec<&'static str> {
+        self.writes
+            .difference(&self.declared_writes)
+            .copied()
+            .collect()
+    }
+
+    // Resources declared as read only that the system never accessed
+    pub fn over_declared_reads(&self) -> Vec<&'static str> {
+        self.declared_reads
+            .iter()
+            .filter(|x| !self.declared_writes.contains(*x))
+            .filter(|x| !self.reads.contains(*x) && !self.writes.contains(*x))
+            .copied()
+            .collect()
+    }
+
+    // Resources declared as written that the system never accessed mutably
+    pub fn over_declared_writes(&self) -> Vec<&'static str> {
+        self.declared_writes
+            .difference(&self.writes)
+            .copied()
+            .collect()
+    }
+
+    // Check if the declared accesses match the actual accesses exactly
+    pub fn is_exact(&self) -> bool {
+        self.under_declared_reads().is_empty()
+            && self.under_declared_writes().is_empty()
+            && self.over_declared_reads().is_empty()
+            && self.over_declared_writes().is_empty()
+    }
+}
+
+// Audit results of all the systems that ran since the dispatcher was built
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    pub systems: Vec<SystemAudit>,
+}
+
+impl AuditReport {
+    // Get the audit of a specific system
+    pub fn get(&self, stage: StageId) -> Option<&SystemAudit> {
+        self.systems.iter().find(|x| x.stage == stage)
+    }
+
+    // Check if every single system declared exactly what it accessed
+    pub fn is_exact(&self) -> bool {
+        self.systems.iter().all(SystemAudit::is_exact)
+    }
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for system in self.systems.iter().filter(|x| !x.is_exact()) {
+            writeln!(f, "{:?}:", system.stage)?;
+
+            let lists = [
+                ("under-declared reads", system.under_declared_reads()),
+                ("under-declared writes", system.under_declared_writes()),
+                ("over-declared reads", system.over_declared_reads()),
+                ("over-declared writes", system.over_declared_writes()),
+            ];
+
+            for (label, names) in lists.iter().filter(|x| !x.1.is_empty()) {
+                writeln!(f, "    {label}: {}", names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use std::any::TypeId;
+
+use crate::{
+    Resource, ResourceIds, ResourceMask, ResourceMaskError, SystemParam, SystemParamError, World,
+};
+
+// A single deferred change to the world
+pub(crate) enum Command {
+    Insert(TypeId, Box<dyn Resource>),
+    Remove(TypeId),
+}
+
+// Queue of deferred resource insertions/removals. The dispatcher applies the queue after the last group
+// Since the changes are deferred, systems don't need to declare any accesses for the resources they insert/remove
+pub struct Commands<'w> {
+    world: &'w World,
+    order: usize,
+}
+
+impl<'w> Commands<'w> {
+    pub(crate) fn new(world: &'w World, order: usize) -> Self {
+        Self { world, order }
+    }
+
+    // Insert a resource into the world, overwriting the old value if there was one
+    pub fn insert<R: Resource>(&mut self, resource: R) {
+        let command = Command::Insert(TypeId::of::<R>(), Box::new(resource));
+        self.world.commands.lock().push((self.order, command));
+    }
+
+    // Remove a resource from the world (does nothing if it isn't present)
+    pub fn remove<R: Resource>(&mut self) {
+        let command = Command::Remove(TypeId::of::<R>());
+        self.world.commands.lock().push((self.order, command));
+    }
+}
+
+impl SystemParam for Commands<'_> {
+    type Item<'w> = Commands<'w>;
+
+    fn access(
+        _: &mut ResourceIds,
+        _: &mut ResourceMask,
+        _: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError> {
+        Ok(())
+    }
+
+    fn fetch(world: &World) -> Result<Self::Item<'_>, SystemParamError> {
+        Ok(world.commands())
+    }
+}
@@ -1,87 +1,806 @@
 use std::{
+    ops::Range,
+    panic::AssertUnwindSafe,
+    path::Path,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Barrier,
     },
     thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-use crate::{Internal, InternalData, World};
+use ahash::AHashMap;
+use parking_lot::{Mutex, RwLock};
+use petgraph::Graph;
+
+use crate::{
+    stage::Callback,
+    steal::{Location, TaskGraph},
+    AuditReport, DispatchError, FailureCause, FrameStats, GroupingStrategy, Internal, InternalData,
+    Phase, Profiler, Registry, RegistrySortingError, ResourceIds, ResourceMask, RuleOrigin,
+    StageId, SystemAudit, SystemFailure, SystemPanic, ThreadTimings, TraceSlice, Tracer, World,
+};
+
+// What the dispatcher should do when one of the systems panics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    // Skip all the remaining groups of the current dispatch
+    AbortFrame,
+
+    // Keep executing the current dispatch, but never execute the panicking system again
+    Disable,
+
+    // Finish the current dispatch, then resume the panic on the thread that called dispatch
+    // The resumed payload is a SystemPanic, which contains the stage of the system alongside the original payload
+    // Only the first panic gets resumed. Every other failure of that dispatch (errors and panics) is logged and dropped
+    #[default]
+    Resume,
+}
+
+// How the dispatcher executes the systems of each dispatch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Executor {
+    // Every system executes on a fixed thread within a fixed group, and all the threads wait for each other after every group
+    #[default]
+    Barrier,
+
+    // Systems execute as soon as the systems they depend on (through rules or conflicting accesses) finished
+    // Each thread has a queue of ready systems, and threads that run out of systems steal them from the other queues
+    WorkStealing,
+}
+
+// Settings that the DispatchBuilder passes down to the dispatcher
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DispatchSettings {
+    pub audit: bool,
+    pub panic: PanicPolicy,
+    pub executor: Executor,
+
+    // Size of the rolling window used by the profiler (if enabled)
+    pub profile: Option<usize>,
+}
+
+// State shared between the dispatcher and all of its threads
+pub(crate) struct Shared {
+    // Only set while a dispatch is running, so the dispatcher holds the sole reference in between dispatches
+    pub world: Mutex<Option<Arc<World>>>,
+    pub resources: RwLock<Arc<ResourceIds>>,
+
+    // Systems of each thread (one entry per group). Each thread locks its own column while dispatching,
+    // and the dispatcher locks all of them in between dispatches to modify the schedule
+    pub columns: Vec<Mutex<Vec<Vec<Internal>>>>,
+
+    // Systems that execute on the thread that calls dispatch (one entry per group)
+    pub main: Mutex<Vec<Vec<Internal>>>,
+
+    // Groups that the worker threads execute in between the current pair of global barriers
+    pub segment: Mutex<Range<usize>>,
+
+    // Dependencies between the systems (only used by the work-stealing executor)
+    pub tasks: RwLock<TaskGraph>,
+    pub settings: DispatchSettings,
+    pub group_barrier: Barrier,
+    pub global_barrier: Barrier,
+    pub stop: AtomicBool,
+    pub aborted: AtomicBool,
+    pub audits: Option<Mutex<AHashMap<StageId, SystemAudit>>>,
+    pub failures: Mutex<Vec<SystemFailure>>,
+    pub profiler: Option<Mutex<Profiler>>,
+    pub tracer: Mutex<Option<Tracer>>,
+    pub tracing: AtomicBool,
+}
 
 pub struct Dispatcher {
+    pub(crate) world: Arc<World>,
+    pub(crate) phases: Vec<Phase>,
+    pub(crate) grouping: Option<Arc<dyn GroupingStrategy>>,
     pub(crate) handles: Vec<JoinHandle<()>>,
-    pub(crate) global_barrier: Arc<Barrier>,
-    pub(crate) var: Arc<AtomicBool>,
+    pub(crate) shared: Arc<Shared>,
 }
 
 impl Dispatcher {
-    pub(crate) fn build(per_thread: Vec<Vec<Option<Internal>>>, world: Arc<World>) -> Self {
+    pub(crate) fn build(
+        per_thread: Vec<Vec<Vec<Internal>>>,
+        main: Vec<Vec<Internal>>,
+        graph: &Graph<StageId, RuleOrigin>,
+        world: Arc<World>,
+        resources: Arc<ResourceIds>,
+        phases: Vec<Phase>,
+        settings: DispatchSettings,
+    ) -> Self {
+        let audits = settings
+            .audit
+            .then(|| Mutex::new(empty_audits(&per_thread, &main, &resources)));
+
+        // There is one column per requested thread (even if it is empty), so rebuilding can spread systems over all of them
+        let groups = main.len();
         let total = per_thread.len();
+        let tasks = TaskGraph::new(graph, &per_thread, &main);
         log::debug!("Total: {total}");
-        let var = Arc::new(AtomicBool::new(false));
-        let group_barrier = Arc::new(Barrier::new(total));
-        let global_barrier = Arc::new(Barrier::new(total + 1));
+        let shared = Arc::new(Shared {
+            world: Mutex::new(None),
+            resources: RwLock::new(resources),
+            columns: per_thread.into_iter().map(Mutex::new).collect(),
+            main: Mutex::new(main),
+            segment: Mutex::new(0..0),
+            tasks: RwLock::new(tasks),
+            settings,
+            group_barrier: Barrier::new(total + 1),
+            global_barrier: Barrier::new(total + 1),
+            stop: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
+            audits,
+            failures: Mutex::new(Vec::new()),
+            profiler: settings
+                .profile
+                .map(|window| Mutex::new(Profiler::new(window, total + 1, groups))),
+            tracer: Mutex::new(None),
+            tracing: AtomicBool::new(false),
+        });
         let mut handles = Vec::<JoinHandle<()>>::new();
 
-        for (i, mut data) in per_thread.into_iter().enumerate() {
-            let group_barrier = group_barrier.clone();
-            let global_barrier = global_barrier.clone();
-            let world = world.clone();
+        for i in 0..total {
+            let shared = shared.clone();
             let name = format!("thread-{i}");
             log::debug!("Spawning dispatcher thread '{}'", &name);
             let builder = std::thread::Builder::new().name(name);
-            let var = var.clone();
             let handle = builder
                 .spawn(move || loop {
-                    global_barrier.wait();
+                    shared.global_barrier.wait();
 
-                    if var.load(Ordering::Relaxed) {
+                    if shared.stop.load(Ordering::Relaxed) {
                         break;
                     }
 
-                    for group in data.iter_mut() {
-                        group_barrier.wait();
-                        if let Some(Internal {
-                            boxed,
-                            reads,
-                            writes,
-                            ..
-                        }) = group
-                        {
-                            let data = InternalData {
-                                read: *reads,
-                                write: *writes,
-                            };
-
-                            world.set_internal(Some(data));
-                            boxed(&world);
-                        }
-                        group_barrier.wait();
+                    let world = shared.world.lock().clone().unwrap();
+                    let resources = shared.resources.read().clone();
+                    let segment = shared.segment.lock().clone();
+                    if shared.settings.executor == Executor::WorkStealing {
+                        execute_tasks(&shared, &world, &resources, &segment, i, &mut []);
+                    } else {
+                        let mut data = shared.columns[i].lock();
+                        let groups = data[segment.clone()].iter_mut().map(Vec::as_mut_slice);
+                        execute_groups(&shared, &world, &resources, segment.zip(groups), i);
                     }
 
-                    global_barrier.wait();
+                    // Release our world reference before the dispatcher regains exclusive access
+                    drop(world);
+                    shared.global_barrier.wait();
                 })
                 .unwrap();
             handles.push(handle);
         }
 
         Self {
+            world,
+            phases,
+            grouping: None,
             handles,
-            global_barrier,
-            var,
+            shared,
+        }
+    }
+
+    // Execute all the systems once, then apply the commands that the systems queued and rotate the event buffers. Systems that fail do not stop the other systems from executing (unless
+    // the panic policy says otherwise), and all the failures of this dispatch get collected into the returned DispatchError
+    pub fn dispatch(&mut self) -> Result<(), DispatchError> {
+        let tracing = self
+            .shared
+            .tracer
+            .lock()
+            .as_ref()
+            .is_some_and(Tracer::recording);
+        self.shared.tracing.store(tracing, Ordering::Relaxed);
+        self.shared.aborted.store(false, Ordering::Relaxed);
+        let start = Instant::now();
+        let resources = self.shared.resources.read().clone();
+        let threads = self.shared.columns.len();
+        let mut main = self.shared.main.lock();
+
+        let mut first = 0;
+        while first < main.len() {
+            // All the groups up until the next exclusive system execute on the worker threads as usual
+            let last = main[first..]
+                .iter()
+                .position(|x| x.iter().any(Internal::exclusive))
+                .map_or(main.len(), |x| first + x);
+
+            if first < last {
+                let segment = first..last;
+                if self.shared.settings.executor == Executor::WorkStealing {
+                    self.shared.tasks.read().prepare(&segment);
+                }
+
+                *self.shared.segment.lock() = segment.clone();
+                *self.shared.world.lock() = Some(self.world.clone());
+                self.shared.global_barrier.wait();
+
+                // Execute the main thread systems in lockstep with the worker threads (or alongside them when work-stealing)
+                if self.shared.settings.executor == Executor::WorkStealing {
+                    execute_tasks(
+                        &self.shared,
+                        &self.world,
+                        &resources,
+                        &segment,
+                        threads,
+                        &mut main,
+                    );
+                } else {
+                    let groups = main[segment.clone()].iter_mut().map(Vec::as_mut_slice);
+                    execute_groups(
+                        &self.shared,
+                        &self.world,
+                        &resources,
+                        segment.zip(groups),
+                        threads,
+                    );
+                }
+                self.world.set_internal(None);
+
+                self.shared.global_barrier.wait();
+                self.shared.world.lock().take();
+            }
+
+            // The worker threads are idle and dropped their reference to the world, so the exclusive system can borrow it mutably
+            if let Some(group) = main.get_mut(last) {
+                execute_exclusive(&self.shared, &mut self.world, &resources, group);
+            }
+            first = last + 1;
+        }
+        drop(main);
+
+        // Sync point: every thread is idle now, so no system can hold a guard to any resource
+        self.world.apply_commands();
+        self.world.update_events();
+        let elapsed = start.elapsed();
+
+        if let Some(profiler) = self.shared.profiler.as_ref() {
+            profiler.lock().record_frame(elapsed);
+        }
+
+        if tracing {
+            if let Some(tracer) = self.shared.tracer.lock().as_mut() {
+                tracer.record_frame(start, elapsed);
+            }
+        }
+
+        let mut failures = std::mem::take(&mut *self.shared.failures.lock());
+        if self.shared.settings.panic == PanicPolicy::Resume {
+            let panicked = failures
+                .iter()
+                .position(|x| matches!(x.cause, FailureCause::Panic(_)));
+            if let Some(index) = panicked {
+                let SystemFailure { stage, cause } = failures.remove(index);
+                let FailureCause::Panic(payload) = cause else {
+                    unreachable!()
+                };
+
+                // Only a single panic can be resumed, so the other failures of this dispatch never reach the caller
+                for failure in failures.iter() {
+                    log::error!(
+                        "Dropping failure of system {:?} ({})",
+                        failure.stage,
+                        failure.cause
+                    );
+                }
+
+                log::error!("Resuming panic of system {stage:?}");
+                std::panic::resume_unwind(Box::new(SystemPanic { stage, payload }));
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(DispatchError { failures })
+        }
+    }
+
+    // Get the world that the systems execute on
+    pub fn world(&self) -> &Arc<World> {
+        &self.world
+    }
+
+    // Get exclusive access to the world in between dispatches (to insert/remove/replace resources directly)
+    // Returns None if something outside of the dispatcher still holds a clone of the world's Arc
+    pub fn world_mut(&mut self) -> Option<&mut World> {
+        Arc::get_mut(&mut self.world)
+    }
+
+    // Execute a closure with exclusive access to the world. Returns None if the world is shared (see Dispatcher::world_mut)
+    pub fn with_world<T>(&mut self, function: impl FnOnce(&mut World) -> T) -> Option<T> {
+        self.world_mut().map(function)
+    }
+
+    // Enable or disable a system without rebuilding the schedule. Disabled systems keep their place in their group
+    // This also re-enables systems that were disabled by PanicPolicy::Disable. Returns false if there is no such system
+    pub fn set_enabled(&mut self, stage: StageId, enabled: bool) -> bool {
+        let found = self.shared.columns.iter().any(|column| {
+            let mut column = column.lock();
+            let internal = column.iter_mut().flatten().find(|x| x.stage == stage);
+            internal.map(|x| x.enabled = enabled).is_some()
+        });
+
+        let mut main = self.shared.main.lock();
+        let internal = main.iter_mut().flatten().find(|x| x.stage == stage);
+        found || internal.map(|x| x.enabled = enabled).is_some()
+    }
+
+    // Remove a system from the schedule, leaving an empty slot in its group (use Dispatcher::rebuild to re-balance)
+    // Rules that other systems had on the removed system get dropped as well. Returns false if there is no such system
+    pub fn remove(&mut self, stage: StageId) -> bool {
+        let mut removed = false;
+        for column in self.shared.columns.iter() {
+            for group in column.lock().iter_mut() {
+                let count = group.len();
+                group.retain(|x| x.stage != stage);
+                removed |= group.len() != count;
+                group.iter_mut().for_each(|x| x.forget(stage));
+            }
+        }
+
+        for group in self.shared.main.lock().iter_mut() {
+            let count = group.len();
+            group.retain(|x| x.stage != stage);
+            removed |= group.len() != count;
+            group.iter_mut().for_each(|x| x.forget(stage));
+        }
+        removed
+    }
+
+    // Re-sort and re-balance the schedule while keeping the worker threads alive
+    // The closure gets a registry containing all the current systems, where it can insert, remove or modify systems
+    // If profiling is enabled, the measured wall times become the costs of the systems (see DispatchBuilder::learn)
+    // Audits and profiler statistics restart from scratch. If sorting fails, the systems that are still in the registry
+    // are put back into their previous place and new systems are dropped
+    pub fn rebuild(
+        &mut self,
+        changes: impl FnOnce(&mut Registry),
+    ) -> Result<(), RegistrySortingError> {
+        let threads = self.handles.len();
+        let mut columns = self
+            .shared
+            .columns
+            .iter()
+            .map(|x| x.lock())
+            .collect::<Vec<_>>();
+        let mut main = self.shared.main.lock();
+        let layout = columns
+            .iter()
+            .map(|x| {
+                x.iter()
+                    .map(|x| x.iter().map(|x| x.stage).collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let main_layout = main
+            .iter()
+            .map(|x| x.iter().map(|x| x.stage).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let systems = columns
+            .iter_mut()
+            .flat_map(|x| x.drain(..))
+            .flatten()
+            .chain(main.drain(..).flatten())
+            .map(|x| (x.stage, x))
+            .collect::<AHashMap<_, _>>();
+
+        let resources = (**self.shared.resources.read()).clone();
+        let mut registry = Registry::from_parts(
+            systems,
+            resources,
+            self.phases.clone(),
+            self.grouping.clone(),
+        );
+        changes(&mut registry);
+
+        let plan = match registry.plan() {
+            Ok(plan) => plan,
+            Err(err) => {
+                let mut systems = registry.into_systems();
+                for (column, stages) in columns.iter_mut().zip(layout) {
+                    column.extend(
+                        stages
+                            .iter()
+                            .map(|x| x.iter().filter_map(|x| systems.remove(x)).collect()),
+                    );
+                }
+                for stages in main_layout {
+                    main.push(stages.iter().filter_map(|x| systems.remove(x)).collect());
+                }
+                return Err(err);
+            }
+        };
+
+        // Systems that executed since the last rebuild get balanced according to their measured wall times
+        let mut builder = registry.into_builder(plan);
+        if let Some(profiler) = self.shared.profiler.as_ref() {
+            builder.learn(&profiler.lock().stats());
+        }
+        builder.balance(Some(threads));
+        let per_thread = std::mem::take(&mut builder.per_thread);
+        let groups = builder.main.len();
+        *self.shared.tasks.write() = TaskGraph::new(&builder.graph, &per_thread, &builder.main);
+
+        if let Some(audits) = self.shared.audits.as_ref() {
+            *audits.lock() = empty_audits(&per_thread, &builder.main, &builder.resources);
+        }
+
+        if let (Some(profiler), Some(window)) =
+            (self.shared.profiler.as_ref(), self.shared.settings.profile)
+        {
+            *profiler.lock() = Profiler::new(window, threads + 1, groups);
         }
+
+        for (column, systems) in columns.iter_mut().zip(per_thread) {
+            **column = systems;
+        }
+        *main = std::mem::take(&mut builder.main);
+        *self.shared.resources.write() = builder.resources;
+        self.phases = builder.phases;
+        self.grouping = builder.grouping;
+        Ok(())
     }
 
-    pub fn dispatch(&mut self) {
-        self.global_barrier.wait();
-        self.global_barrier.wait();
+    // Get the audit results of all the dispatches so far. Returns None if auditing was not enabled on the builder
+    pub fn audit(&self) -> Option<AuditReport> {
+        let audits = self.shared.audits.as_ref()?.lock();
+        let mut systems = audits.values().cloned().collect::<Vec<_>>();
+        systems.sort_by_key(|x| x.stage);
+        Some(AuditReport { systems })
     }
+
+    // Get the timings of the last few dispatches. Returns None if profiling was not enabled on the builder
+    pub fn stats(&self) -> Option<FrameStats> {
+        Some(self.shared.profiler.as_ref()?.lock().stats())
+    }
+
+    // Start recording the timelines of the next "frames" dispatches. This discards any previously recorded trace
+    pub fn record_trace(&mut self, frames: usize) {
+        *self.shared.tracer.lock() = Some(Tracer::new(self.handles.len(), frames));
+    }
+
+    // Get the recorded trace in the Chrome Trace Event Format, which can be opened in chrome://tracing or Perfetto
+    // Returns None if Dispatcher::record_trace was never called
+    pub fn trace(&self) -> Option<String> {
+        self.shared.tracer.lock().as_ref().map(Tracer::json)
+    }
+
+    // Write the recorded trace to a JSON file
+    pub fn write_trace(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let json = self
+            .trace()
+            .ok_or_else(|| std::io::Error::other("No trace was recorded"))?;
+        std::fs::write(path, json)
+    }
+}
+
+// World that a single system executes on. Only exclusive systems get to borrow it mutably
+enum Target<'a> {
+    Shared(&'a World),
+    Exclusive(&'a mut World),
+}
+
+impl Target<'_> {
+    fn world(&self) -> &World {
+        match self {
+            Target::Shared(world) => world,
+            Target::Exclusive(world) => world,
+        }
+    }
+}
+
+// Execute a single system on the current thread, catching any errors or panics that it might cause
+// Returns when the system started and its wall time
+fn execute(
+    shared: &Shared,
+    mut target: Target<'_>,
+    resources: &Arc<ResourceIds>,
+    internal: &mut Internal,
+) -> Option<(Instant, Duration)> {
+    let stage = internal.stage;
+    let exclusive = internal.exclusive();
+
+    // Exclusive systems can access everything, so there is nothing to audit either
+    let tick = target.world().next_tick();
+    let data = InternalData {
+        read: if exclusive {
+            ResourceMask::FULL
+        } else {
+            internal.reads
+        },
+        write: if exclusive {
+            ResourceMask::FULL
+        } else {
+            internal.writes
+        },
+        resources: resources.clone(),
+        order: internal.order,
+        tick,
+        last_run: internal.last_run,
+        accesses: (shared.audits.is_some() && !exclusive).then(Vec::new),
+    };
+
+    let (conditions, boxed) = (&mut internal.conditions, &mut internal.boxed);
+    let start = Instant::now();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let world = target.world();
+
+        // Run conditions can read any resource without declaring it, but they can't write to any
+        if !conditions.is_empty() {
+            world.set_internal(Some(InternalData {
+                read: ResourceMask::FULL,
+                write: ResourceMask::EMPTY,
+                accesses: None,
+                ..data.clone()
+            }));
+
+            if !conditions.iter_mut().all(|condition| condition(world)) {
+                return None;
+            }
+        }
+
+        world.set_internal(Some(data));
+        Some(match (boxed, &mut target) {
+            (Callback::Shared(boxed), Target::Shared(world)) => boxed(world),
+            (Callback::Shared(boxed), Target::Exclusive(world)) => boxed(world),
+            (Callback::Exclusive(boxed), Target::Exclusive(world)) => boxed(world),
+            (Callback::Exclusive(_), Target::Shared(_)) => unreachable!(),
+        })
+    }));
+    let elapsed = start.elapsed();
+
+    let result = match result {
+        Ok(None) => return None,
+        Ok(Some(result)) => Ok(result),
+        Err(payload) => Err(payload),
+    };
+    internal.last_run = tick;
+
+    if let Some(audits) = shared.audits.as_ref() {
+        let records = target.world().take_accesses();
+        audits.lock().get_mut(&stage).unwrap().record(records);
+    }
+
+    let cause = match result {
+        Ok(Ok(())) => return Some((start, elapsed)),
+        Ok(Err(error)) => {
+            log::error!("System {stage:?} failed: {error}");
+            FailureCause::Error(error)
+        }
+        Err(payload) => {
+            log::error!("System {stage:?} panicked");
+            match shared.settings.panic {
+                PanicPolicy::AbortFrame => shared.aborted.store(true, Ordering::Relaxed),
+                PanicPolicy::Disable => internal.enabled = false,
+                PanicPolicy::Resume => {}
+            }
+            FailureCause::Panic(payload)
+        }
+    };
+
+    shared.failures.lock().push(SystemFailure { stage, cause });
+    Some((start, elapsed))
+}
+
+// Execute the groups of a single thread, waiting for all the other threads before and after each group
+// Each thread executes the systems that were packed onto it for that group one after another
+fn execute_groups<'a>(
+    shared: &Shared,
+    world: &World,
+    resources: &Arc<ResourceIds>,
+    groups: impl Iterator<Item = (usize, &'a mut [Internal])>,
+    thread: usize,
+) {
+    let tracing = shared.tracing.load(Ordering::Relaxed);
+    let mut timings = ThreadTimings::default();
+    let mut slices = Vec::<TraceSlice>::new();
+
+    for (index, group) in groups {
+        let start = Instant::now();
+        shared.group_barrier.wait();
+        let mut idle = start.elapsed();
+        if tracing {
+            slices.push(TraceSlice::barrier(index, start, idle));
+        }
+
+        for internal in group.iter_mut() {
+            if internal.enabled && !shared.aborted.load(Ordering::Relaxed) {
+                let target = Target::Shared(world);
+                if let Some((start, elapsed)) = execute(shared, target, resources, internal) {
+                    timings.systems.push((internal.stage, elapsed));
+                    if tracing {
+                        slices.push(TraceSlice::system(internal.stage.name, start, elapsed));
+                    }
+                }
+            }
+        }
+
+        let start = Instant::now();
+        shared.group_barrier.wait();
+        let waited = start.elapsed();
+        idle += waited;
+        timings.idle.push((index, idle));
+        if tracing {
+            slices.push(TraceSlice::barrier(index, start, waited));
+        }
+    }
+
+    if let Some(profiler) = shared.profiler.as_ref() {
+        profiler.lock().record_thread(thread, timings);
+    }
+
+    if tracing {
+        if let Some(tracer) = shared.tracer.lock().as_mut() {
+            tracer.record_thread(thread, slices);
+        }
+    }
+}
+
+// Execute the tasks of a segment as soon as they are ready, stealing tasks from the other threads whenever we run out
+// Only the thread that calls dispatch gets the main thread systems, which it executes alongside any worker system
+fn execute_tasks(
+    shared: &Shared,
+    world: &World,
+    resources: &Arc<ResourceIds>,
+    segment: &Range<usize>,
+    thread: usize,
+    main: &mut [Vec<Internal>],
+) {
+    let tracing = shared.tracing.load(Ordering::Relaxed);
+    let tasks = shared.tasks.read();
+    let mut timings = ThreadTimings::default();
+    let mut slices = Vec::<TraceSlice>::new();
+
+    let mut run = |internal: &mut Internal| {
+        if !internal.enabled || shared.aborted.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some((start, elapsed)) = execute(shared, Target::Shared(world), resources, internal)
+        {
+            timings.systems.push((internal.stage, elapsed));
+            if tracing {
+                slices.push(TraceSlice::system(internal.stage.name, start, elapsed));
+            }
+        }
+    };
+
+    // Time spent sleeping counts as idle time of the group of the next task (or of the last group of the segment)
+    let mut idle = vec![Duration::ZERO; segment.len()];
+    let mut waited = Duration::ZERO;
+    while !tasks.done() {
+        let Some(index) = tasks.next(thread) else {
+            let start = Instant::now();
+            tasks.wait(thread);
+            waited += start.elapsed();
+            continue;
+        };
+
+        // Commands are ordered by where the system is placed (see Internal::order), not by the thread that executed it
+        let task = &tasks.tasks[index];
+        idle[task.group - segment.start] += std::mem::take(&mut waited);
+        match task.location {
+            // Take the system out of its column, so other threads can keep on modifying the column in the meantime
+            Location::Worker(column, group) => {
+                let taken = {
+                    let mut column = shared.columns[column].lock();
+                    let position = column[group].iter().position(|x| x.stage == task.stage);
+                    position.map(|x| column[group].remove(x))
+                };
+                if let Some(mut internal) = taken {
+                    run(&mut internal);
+                    shared.columns[column].lock()[group].push(internal);
+                }
+            }
+
+            Location::Main(group) => {
+                let internal = main
+                    .get_mut(group)
+                    .and_then(|x| x.iter_mut().find(|x| x.stage == task.stage));
+                if let Some(internal) = internal {
+                    run(internal);
+                }
+            }
+        }
+
+        tasks.complete(index, segment);
+    }
+
+    if let Some(last) = idle.last_mut() {
+        *last += waited;
+    }
+    timings.idle.extend(segment.clone().zip(idle));
+
+    if let Some(profiler) = shared.profiler.as_ref() {
+        profiler.lock().record_thread(thread, timings);
+    }
+
+    if tracing {
+        if let Some(tracer) = shared.tracer.lock().as_mut() {
+            tracer.record_thread(thread, slices);
+        }
+    }
+}
+
+// Execute the exclusive systems of a group on the thread that calls dispatch, while the worker threads are idle
+fn execute_exclusive(
+    shared: &Shared,
+    world: &mut Arc<World>,
+    resources: &Arc<ResourceIds>,
+    group: &mut [Internal],
+) {
+    let thread = shared.columns.len();
+    let tracing = shared.tracing.load(Ordering::Relaxed);
+    let mut timings = ThreadTimings::default();
+    let mut slices = Vec::<TraceSlice>::new();
+
+    for internal in group.iter_mut() {
+        if !internal.enabled || shared.aborted.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let Some(world) = Arc::get_mut(world) else {
+            log::error!(
+                "System {:?} needs exclusive access to the world, but it is shared",
+                internal.stage
+            );
+            let failure = SystemFailure {
+                stage: internal.stage,
+                cause: FailureCause::SharedWorld,
+            };
+            shared.failures.lock().push(failure);
+            continue;
+        };
+
+        if let Some((start, elapsed)) =
+            execute(shared, Target::Exclusive(world), resources, internal)
+        {
+            timings.systems.push((internal.stage, elapsed));
+            if tracing {
+                slices.push(TraceSlice::system(internal.stage.name, start, elapsed));
+            }
+        }
+        world.set_internal(None);
+    }
+
+    if let Some(profiler) = shared.profiler.as_ref() {
+        profiler.lock().record_thread(thread, timings);
+    }
+
+    if tracing {
+        if let Some(tracer) = shared.tracer.lock().as_mut() {
+            tracer.record_thread(thread, slices);
+        }
+    }
+}
+
+// Create empty audits for all the systems
+fn empty_audits(
+    per_thread: &[Vec<Vec<Internal>>],
+    main: &[Vec<Internal>],
+    resources: &ResourceIds,
+) -> AHashMap<StageId, SystemAudit> {
+    per_thread
+        .iter()
+        .flatten()
+        .flatten()
+        .chain(main.iter().flatten())
+        .map(|internal| {
+            let reads = resources.names(&internal.reads);
+            let writes = resources.names(&internal.writes);
+            (
+                internal.stage,
+                SystemAudit::new(internal.stage, reads, writes),
+            )
+        })
+        .collect()
 }
 
 impl Drop for Dispatcher {
     fn drop(&mut self) {
-        self.var.store(true, Ordering::Relaxed);
-        self.global_barrier.wait();
+        self.shared.stop.store(true, Ordering::Relaxed);
+        self.shared.global_barrier.wait();
         for thread in self.handles.drain(..) {
             thread.join().unwrap();
         }
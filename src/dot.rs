@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use ahash::AHashMap;
+use petgraph::visit::EdgeRef;
+
+use crate::{DispatchBuilder, InjectionRule, RuleOrigin, StageId};
+
+impl DispatchBuilder {
+    // Export the rule graph and the execution groups in the Graphviz DOT format
+    // Every group is drawn as a cluster, and every system is labelled with the resources it reads/writes
+    pub fn dot(&self) -> String {
+        let mut dot =
+            String::from("digraph schedule {\n    rankdir=LR;\n    node [shape=box];\n\n");
+        let ids = self
+            .graph
+            .node_indices()
+            .map(|index| (self.graph[index], format!("n{}", index.index())))
+            .collect::<AHashMap<StageId, String>>();
+
+        // Anchors (user/post_user) are the only nodes that aren't systems
+        for index in self.graph.node_indices() {
+            let stage = self.graph[index];
+            let id = &ids[&stage];
+            if let Some((reads, writes)) = self.masks.get(&stage) {
+                let only_reads = *reads & !*writes;
+                let mut label = escape(stage.name);
+                for (prefix, mask) in [("R", only_reads), ("W", *writes)] {
+                    if !mask.is_empty() {
+                        let names = self.resources.names(&mask).join(", ");
+                        write!(label, "\\n{prefix}: {}", escape(&names)).unwrap();
+                    }
+                }
+                writeln!(dot, "    {id} [label=\"{label}\"];").unwrap();
+            } else {
+                writeln!(
+                    dot,
+                    "    {id} [label=\"{}\", shape=ellipse, style=dashed];",
+                    escape(stage.name)
+                )
+                .unwrap();
+            }
+        }
+        dot.push('\n');
+
+        for (i, group) in self.execution_matrix_cm.iter().enumerate() {
+            writeln!(dot, "    subgraph cluster_{i} {{").unwrap();
+            writeln!(dot, "        label=\"group {i}\";").unwrap();
+            writeln!(dot, "        style=rounded;").unwrap();
+            for stage in group {
+                writeln!(dot, "        {};", ids[stage]).unwrap();
+            }
+            writeln!(dot, "    }}").unwrap();
+        }
+        dot.push('\n');
+
+        for edge in self.graph.edge_references() {
+            let source = &ids[&self.graph[edge.source()]];
+            let target = &ids[&self.graph[edge.target()]];
+            let label = match edge.weight() {
+                RuleOrigin::Anchor => "",
+                RuleOrigin::Rule(_, InjectionRule::Before(_)) => "before",
+                RuleOrigin::Rule(_, InjectionRule::After(_)) => "after",
+                RuleOrigin::Rule(_, InjectionRule::Parallel(_)) => "parallel",
+                RuleOrigin::Rule(_, InjectionRule::BeforeSet(_)) => "before set",
+                RuleOrigin::Rule(_, InjectionRule::AfterSet(_)) => "after set",
+            };
+            writeln!(dot, "    {source} -> {target} [label=\"{label}\"];").unwrap();
+        }
+
+        // Parallel hints aren't part of the graph, so draw them as undirected dotted edges
+        for group in self.parallel.iter() {
+            for pair in group.windows(2) {
+                let (a, b) = (&ids[&pair[0]], &ids[&pair[1]]);
+                writeln!(
+                    dot,
+                    "    {a} -> {b} [label=\"parallel\", style=dotted, dir=none];"
+                )
+                .unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// Escape a string so it can be used within a quoted DOT label
+fn escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
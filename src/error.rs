@@ -1,6 +1,13 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Display},
+};
 use thiserror::Error;
 
-use crate::StageId;
+use crate::{RuleOrigin, StageId};
+
+// Error returned by a system that failed to execute
+pub type SystemError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Error, Debug)]
 pub enum WorldBorrowError {
@@ -26,16 +33,72 @@ pub enum WorldBorrowMutError {
     BorrowMutError(core::cell::BorrowMutError),
 }
 
+#[derive(Error, Debug)]
+pub enum SystemParamError {
+    #[error("{0}")]
+    Borrow(#[from] WorldBorrowError),
+
+    #[error("{0}")]
+    BorrowMut(#[from] WorldBorrowMutError),
+}
+
+impl SystemParamError {
+    // Check if the parameter failed because the resource is not present in the world
+    pub fn not_present(&self) -> bool {
+        matches!(
+            self,
+            SystemParamError::Borrow(WorldBorrowError::NotPresent)
+                | SystemParamError::BorrowMut(WorldBorrowMutError::NotPresent)
+        )
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RegistrySortingError {
-    #[error("Error while parsing Graph. Possibly due to cyclic reference / rules")]
-    GraphVisitMissingNodes,
+    // Contains every stage of the cycle in order, alongside the rule that created the edge to the next stage
+    #[error("Cyclic rules between stages: {}", format_cycle(.0))]
+    CyclicRules(Vec<(StageId, RuleOrigin)>),
 
-    #[error("Error while parsing Graph. Possibly due to cyclic reference / rules")]
-    UnsatisfiableParallelRules,
+    // Contains the two stages that could not be grouped together and the resources they conflict on
+    #[error("Stages '{0:?}' and '{1:?}' must execute in parallel, but {}", format_conflicts(.2))]
+    UnsatisfiableParallelRules(StageId, StageId, Vec<&'static str>),
 
     #[error("Stage '{0:?}' tried to reference stage '{1:?}', but the latter stage does not exist")]
     MissingStage(StageId, StageId),
+
+    #[error("Stage '{0:?}' has an invalid resource mask: {1}")]
+    InvalidResourceMask(StageId, ResourceMaskError),
+
+    // Contains the stage that the grouping strategy misplaced and the reason why
+    #[error("The grouping strategy misplaced stage '{0:?}': {1}")]
+    InvalidGrouping(StageId, &'static str),
+}
+
+fn format_cycle(cycle: &[(StageId, RuleOrigin)]) -> String {
+    let mut output = String::new();
+    for (stage, origin) in cycle {
+        let rule = match origin {
+            RuleOrigin::Anchor => "anchor".to_string(),
+            RuleOrigin::Rule(declared, rule) => format!("{declared:?} {rule:?}"),
+        };
+        output += &format!("{stage:?} -[{rule}]-> ");
+    }
+
+    if let Some((first, _)) = cycle.first() {
+        output += &format!("{first:?}");
+    }
+    output
+}
+
+fn format_conflicts(conflicts: &[&'static str]) -> String {
+    if conflicts.is_empty() {
+        "they ended up in different groups due to their ordering rules".to_string()
+    } else {
+        format!(
+            "they conflict on the following resources: {}",
+            conflicts.join(", ")
+        )
+    }
 }
 
 #[derive(Error, Debug)]
@@ -49,3 +112,122 @@ pub enum StageError {
     #[error("Tried to insert the stage into the pipeline, but the stage name was already used")]
     Overlapping,
 }
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceMaskError {
+    #[error(
+        "Cannot register resource '{0}', the limit of {1} distinct resource types was reached"
+    )]
+    LimitReached(&'static str, usize),
+
+    #[error("Resource '{0}' is used by multiple parameters of the same system, and at least one of them writes to it")]
+    ConflictingAccess(&'static str),
+}
+
+// Reason why a system failed to execute
+pub enum FailureCause {
+    // The system returned an error
+    Error(SystemError),
+
+    // The system panicked. Contains the panic payload
+    Panic(Box<dyn Any + Send>),
+
+    // The system is exclusive, but something outside of the dispatcher held a clone of the world's Arc
+    SharedWorld,
+}
+
+impl FailureCause {
+    // Get the panic message if the payload is a string (which it is for panic!() and friends)
+    pub fn panic_message(&self) -> Option<&str> {
+        let FailureCause::Panic(payload) = self else {
+            return None;
+        };
+
+        payload_message(&**payload)
+    }
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+impl Debug for FailureCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureCause::Error(error) => f.debug_tuple("Error").field(error).finish(),
+            FailureCause::Panic(_) => f.debug_tuple("Panic").field(&self.panic_message()).finish(),
+            FailureCause::SharedWorld => write!(f, "SharedWorld"),
+        }
+    }
+}
+
+impl Display for FailureCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureCause::Error(error) => write!(f, "{error}"),
+            FailureCause::Panic(_) => match self.panic_message() {
+                Some(message) => write!(f, "panicked with '{message}'"),
+                None => write!(f, "panicked"),
+            },
+            FailureCause::SharedWorld => {
+                write!(f, "could not get exclusive access to the shared world")
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("System {stage:?} failed: {cause}")]
+pub struct SystemFailure {
+    pub stage: StageId,
+    pub cause: FailureCause,
+}
+
+// Payload that PanicPolicy::Resume resumes the panic of a system with, so the caller knows which system panicked
+pub struct SystemPanic {
+    pub stage: StageId,
+
+    // Original payload of the panic
+    pub payload: Box<dyn Any + Send>,
+}
+
+impl SystemPanic {
+    // Get the panic message if the original payload is a string (which it is for panic!() and friends)
+    pub fn message(&self) -> Option<&str> {
+        payload_message(&*self.payload)
+    }
+}
+
+impl Debug for SystemPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemPanic")
+            .field("stage", &self.stage)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl Display for SystemPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "System {:?} panicked with '{message}'", self.stage),
+            None => write!(f, "System {:?} panicked", self.stage),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("{} system(s) failed during dispatch: {:?}", .failures.len(), self.stages())]
+pub struct DispatchError {
+    pub failures: Vec<SystemFailure>,
+}
+
+impl DispatchError {
+    // Get the stage ids of all the systems that failed
+    pub fn stages(&self) -> Vec<StageId> {
+        self.failures.iter().map(|x| x.stage).collect()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Resource;
+
+// Double buffered queue of events that systems can send to each other
+// Events stay readable for two dispatches, so readers that execute before the writer still get to see them
+// The buffers of every channel added through World::add_events are rotated by the dispatcher at the end of each dispatch
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+
+    // Id of the first event within the previous buffer
+    start: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Events<T> {
+    // Send a new event through the channel
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    // Iterate over all the events that are still alive (oldest first)
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    // Number of events that are still alive
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    // Check if there are no alive events
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Drop all the events in both buffers
+    pub fn clear(&mut self) {
+        self.start += self.len();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    // Rotate the buffers, dropping the events that were sent before the last update
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    // Id that the next event sent will have
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+
+    // Type erased version of Events::update that the world can call without knowing the event type
+    pub(crate) fn update_resource(resource: &mut dyn Resource) {
+        resource
+            .as_any_mut()
+            .downcast_mut::<Self>()
+            .unwrap()
+            .update();
+    }
+}
+
+// Keeps track of which events a reader has already seen, so every event gets read exactly once
+#[derive(Default, Clone, Copy, Debug)]
+pub struct EventCursor {
+    next: usize,
+}
+
+impl EventCursor {
+    // Iterate over the events that were sent since the last time this cursor read the channel
+    pub fn read<'a, T: Send + Sync + 'static>(
+        &mut self,
+        events: &'a Events<T>,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        let skip = self.next.saturating_sub(events.start);
+        self.next = events.end();
+        events.iter().skip(skip)
+    }
+}
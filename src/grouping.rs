@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use crate::{ResourceMask, StageId};
+
+// A system as seen by a grouping strategy
+#[derive(Clone, Copy, Debug)]
+pub struct GroupNode {
+    pub stage: StageId,
+
+    // Depth of the system within the rule graph. Systems always have a greater depth than the systems they depend on
+    pub depth: i32,
+    pub reads: ResourceMask,
+    pub writes: ResourceMask,
+
+    // Exclusive systems (see Registry::insert_exclusive) must be the only system of their group
+    pub exclusive: bool,
+
+    // Systems that must execute on the thread that calls dispatch (see InjectionOrder::main_thread)
+    pub main_thread: bool,
+
+    // Declared cost of the system (see InjectionOrder::cost). Measured wall times only get applied after sorting
+    pub cost: Option<Duration>,
+}
+
+impl GroupNode {
+    // Check if the two systems can execute within the same group
+    pub fn compatible(&self, other: &GroupNode) -> bool {
+        !self.exclusive
+            && !other.exclusive
+            && !self.writes.intersects(&(other.reads | other.writes))
+            && !other.writes.intersects(&self.reads)
+    }
+}
+
+// Decides which systems execute in parallel. The strategy receives the systems in topological order and
+// returns the groups in execution order. Registry::sort makes sure that the groups don't break any rule,
+// don't contain conflicting systems and contain every system exactly once
+pub trait GroupingStrategy: Send + Sync {
+    fn group(&self, nodes: &[GroupNode]) -> Vec<Vec<StageId>>;
+}
+
+// Default strategy: put each system into the first group of the same depth that it does not conflict with
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstFit;
+
+impl GroupingStrategy for FirstFit {
+    fn group(&self, nodes: &[GroupNode]) -> Vec<Vec<StageId>> {
+        // Groups for each type of resource access
+        // Must correspond to the "depth" of each nodes as we can't mix and match groups from different levels (otherwise it would fuck
+        // with the first requirement of having proper depedency sorting)
+        let mut groups = Vec::<(i32, ResourceMask, ResourceMask, bool, Vec<StageId>)>::default();
+
+        // this will batch the system into their "group" batches where they can execute in parallel with other systems
+        // without overlapping their resource bitmasks
+        for node in nodes.iter() {
+            let node_reads = node.reads;
+            let node_writes = node.writes;
+            let node_exclusive = node.exclusive;
+            let depth = node.depth;
+            log::debug!(
+                "System: {}, Depth: {} R: {:?}, W: {:?}",
+                node.stage.name,
+                depth,
+                node_reads,
+                node_writes
+            );
+
+            // must find group with the following requirements:
+            // 1) same depth as current node depth
+            // 2) non intersecting read/writes
+            // within a group, there should be shared access to all read resources, but unique access to all write resources
+            let group_index = groups.iter().position(
+                |(group_depth, group_reads, group_writes, group_exclusive, _)| {
+                    // check group depth
+                    let depth = *group_depth == depth;
+
+                    // check for ref-mut collisions
+                    let ref_mut_collisions = !(node_reads | node_writes).intersects(group_writes)
+                        && !node_writes.intersects(group_reads);
+
+                    // check for mut-mut collisions
+                    let mut_mut_collisions = !node_writes.intersects(group_writes);
+
+                    // check for exclusive systems on either side
+                    let exclusive = !node_exclusive && !*group_exclusive;
+
+                    depth && ref_mut_collisions && mut_mut_collisions && exclusive
+                },
+            );
+
+            // if the group is missing, add it, otherwise just modify the current group
+            if let Some(group_index) = group_index {
+                let (_, read, writes, _, stages) = &mut groups[group_index];
+                *read |= node_reads;
+                *writes |= node_writes;
+                stages.push(node.stage);
+            } else {
+                groups.push((
+                    depth,
+                    node_reads,
+                    node_writes,
+                    node_exclusive,
+                    vec![node.stage],
+                ));
+            }
+        }
+
+        for (i, (depth, reads, writes, _, _)) in groups.iter().enumerate() {
+            log::debug!(
+                "Index: {i}, Depth {depth}, R: {:?}, W: {:?}",
+                *reads,
+                *writes
+            )
+        }
+
+        // column based table to know what to execute in parallel
+        groups.sort_by_key(|(depth, _, _, _, _)| *depth);
+        groups
+            .into_iter()
+            .map(|(_, _, _, _, stages)| stages)
+            .collect()
+    }
+}
@@ -1,20 +1,32 @@
+use std::time::Duration;
+
 use crate::{
-    resources::ResourceMask, rules::InjectionRule, stage::StageId, world::World, Internal, Resource,
+    mask::ResourceMask,
+    rules::{InjectionRule, Phase},
+    stage::{SetId, StageId},
+    Internal, Resource, ResourceIds, ResourceMaskError, World,
 };
 
 pub struct InjectionOrder<'a> {
     pub(crate) internal: &'a mut Internal,
+    pub(crate) resources: &'a mut ResourceIds,
     pub(crate) default: bool,
 }
 
 impl<'a> InjectionOrder<'a> {
-    pub(crate) fn new(internal: &'a mut Internal) -> Self {
+    pub(crate) fn new(internal: &'a mut Internal, resources: &'a mut ResourceIds) -> Self {
         Self {
             internal,
+            resources,
             default: true,
         }
     }
 
+    // Get the stage of the system that we are inserting (useful for named instances)
+    pub fn stage(&self) -> StageId {
+        self.internal.stage
+    }
+
     pub fn writes_mask(self, mask: ResourceMask) -> Self {
         self.internal.writes |= mask;
         self.internal.reads |= mask;
@@ -27,11 +39,37 @@ impl<'a> InjectionOrder<'a> {
     }
 
     pub fn writes<R: Resource>(self) -> Self {
-        self.writes_mask(R::mask())
+        match self.resources.mask::<R>() {
+            Ok(mask) => self.writes_mask(mask),
+            Err(err) => self.invalid_mask(err),
+        }
     }
 
     pub fn reads<R: Resource>(self) -> Self {
-        self.reads_mask(R::mask())
+        match self.resources.mask::<R>() {
+            Ok(mask) => self.reads_mask(mask),
+            Err(err) => self.invalid_mask(err),
+        }
+    }
+
+    // Only execute the system when the condition returns true. Multiple conditions must all return true
+    // Conditions can read any resource without declaring it, so they should not read resources that parallel systems write to
+    pub fn run_if<C: FnMut(&World) -> bool + Sync + Send + 'static>(self, condition: C) -> Self {
+        self.internal.conditions.push(Box::new(condition));
+        self
+    }
+
+    // Declare how long the system usually takes, so balancing can keep expensive systems apart
+    // Costs that the profiler measures replace this once the dispatcher gets rebuilt (see DispatchBuilder::learn)
+    pub fn cost(self, cost: Duration) -> Self {
+        self.internal.cost = Some(cost);
+        self
+    }
+
+    // Keep track of the error so we can report it when sorting the registry
+    fn invalid_mask(self, err: ResourceMaskError) -> Self {
+        self.internal.mask_error.get_or_insert(err);
+        self
     }
 
     fn reset_defaults(&mut self) {
@@ -40,7 +78,7 @@ impl<'a> InjectionOrder<'a> {
         }
     }
 
-    pub fn before<S: FnMut(&World) + 'static>(mut self, system: S) -> Self {
+    pub fn before<S: 'static>(mut self, system: S) -> Self {
         self.reset_defaults();
         self.internal
             .rules
@@ -48,7 +86,7 @@ impl<'a> InjectionOrder<'a> {
         self
     }
 
-    pub fn after<S: FnMut(&World) + 'static>(mut self, system: S) -> Self {
+    pub fn after<S: 'static>(mut self, system: S) -> Self {
         self.reset_defaults();
         self.internal
             .rules
@@ -56,10 +94,54 @@ impl<'a> InjectionOrder<'a> {
         self
     }
 
-    pub fn parallel<S: FnMut(&World) + 'static>(self, system: S) -> Self {
+    pub fn parallel<S: 'static>(self, system: S) -> Self {
         self.internal
             .rules
             .push(InjectionRule::Parallel(StageId::of(&system)));
         self
     }
+
+    // Add the system to a set, so other systems can order themselves against the whole set
+    pub fn in_set<L: 'static>(self, label: L) -> Self {
+        let set = SetId::of(&label);
+        if !self.internal.sets.contains(&set) {
+            self.internal.sets.push(set);
+        }
+        self
+    }
+
+    // Execute the system on the thread that calls Dispatcher::dispatch instead of a worker thread
+    // Only main thread systems can access non-send resources (see World::insert_non_send)
+    pub fn main_thread(self) -> Self {
+        self.internal.main_thread = true;
+        self
+    }
+
+    // Place the system into a phase (see Registry::add_phase) instead of the built-in user phase
+    // The system also becomes part of the set with the same label, so other systems can order themselves against the phase
+    pub fn in_phase<L: 'static>(mut self, label: L) -> Self {
+        let phase = Phase::of(&label);
+        self.reset_defaults();
+        self.internal.rules.push(InjectionRule::After(phase.start));
+        self.internal.rules.push(InjectionRule::Before(phase.end));
+        self.in_set(label)
+    }
+
+    // Execute before every system of the set (except this one if it is part of the set)
+    pub fn before_set<L: 'static>(mut self, label: L) -> Self {
+        self.reset_defaults();
+        self.internal
+            .rules
+            .push(InjectionRule::BeforeSet(SetId::of(&label)));
+        self
+    }
+
+    // Execute after every system of the set (except this one if it is part of the set)
+    pub fn after_set<L: 'static>(mut self, label: L) -> Self {
+        self.reset_defaults();
+        self.internal
+            .rules
+            .push(InjectionRule::AfterSet(SetId::of(&label)));
+        self
+    }
 }
@@ -1,21 +1,39 @@
+mod audit;
+mod commands;
 mod dispatcher;
+mod dot;
 mod error;
+mod events;
+mod grouping;
 mod guards;
 mod inject;
+mod mask;
+mod param;
 mod resources;
 mod rules;
 mod sorted;
 mod stage;
+mod stats;
+mod steal;
+mod trace;
 mod unsorted;
 mod world;
 
+pub use audit::*;
+pub use commands::*;
 pub use dispatcher::*;
 pub use error::*;
+pub use events::*;
+pub use grouping::*;
 pub use guards::*;
 pub use inject::*;
+pub use mask::*;
+pub use param::*;
 pub use resources::*;
 pub use rules::*;
 pub use sorted::*;
 pub use stage::*;
+pub use stats::*;
+pub(crate) use trace::*;
 pub use unsorted::*;
 pub use world::*;
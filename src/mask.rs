@@ -0,0 +1,113 @@
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not},
+};
+
+const WORDS: usize = 8;
+
+// A fixed size bitset where each bit represents a single resource type
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ResourceMask([u64; WORDS]);
+
+impl ResourceMask {
+    // Maximum number of distinct resource types that a mask can represent
+    pub const BITS: usize = WORDS * 64;
+
+    // Mask with no resources set
+    pub const EMPTY: Self = Self([0; WORDS]);
+
+    // Mask with every single resource set
+    pub const FULL: Self = Self([u64::MAX; WORDS]);
+
+    // Create a mask with a single bit set. Returns None if the bit is out of range
+    pub fn from_bit(bit: usize) -> Option<Self> {
+        let mut mask = Self::EMPTY;
+        mask.set(bit).then_some(mask)
+    }
+
+    // Set a bit in the mask. Returns false if the bit is out of range
+    pub fn set(&mut self, bit: usize) -> bool {
+        if bit >= Self::BITS {
+            return false;
+        }
+
+        self.0[bit / 64] |= 1 << (bit % 64);
+        true
+    }
+
+    // Check if a specific bit is set
+    pub fn contains_bit(&self, bit: usize) -> bool {
+        bit < Self::BITS && (self.0[bit / 64] >> (bit % 64)) & 1 == 1
+    }
+
+    // Check if the mask has no bits set
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|x| *x == 0)
+    }
+
+    // Check if the two masks share any bits
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.0.iter().zip(other.0.iter()).any(|(a, b)| a & b != 0)
+    }
+
+    // Number of bits set in the mask
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|x| x.count_ones() as usize).sum()
+    }
+
+    // Iterate over the indices of all the bits that are set
+    pub fn bits(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::BITS).filter(|bit| self.contains_bit(*bit))
+    }
+}
+
+impl Debug for ResourceMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.bits()).finish()
+    }
+}
+
+impl BitOr for ResourceMask {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self::Output {
+        self |= rhs;
+        self
+    }
+}
+
+impl BitOrAssign for ResourceMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a |= b;
+        }
+    }
+}
+
+impl BitAnd for ResourceMask {
+    type Output = Self;
+
+    fn bitand(mut self, rhs: Self) -> Self::Output {
+        self &= rhs;
+        self
+    }
+}
+
+impl BitAndAssign for ResourceMask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a &= b;
+        }
+    }
+}
+
+impl Not for ResourceMask {
+    type Output = Self;
+
+    fn not(mut self) -> Self::Output {
+        for a in self.0.iter_mut() {
+            *a = !*a;
+        }
+        self
+    }
+}
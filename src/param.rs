@@ -0,0 +1,144 @@
+use std::any::type_name;
+
+use crate::{
+    Read, Resource, ResourceIds, ResourceMask, ResourceMaskError, SystemError, SystemOutput,
+    SystemParamError, World, Write,
+};
+
+// A value that can be fetched from the world and passed as an argument to a typed system
+// Each parameter declares the resources it accesses so the registry can derive the system masks
+pub trait SystemParam {
+    type Item<'w>;
+
+    // Add the accesses of this parameter to the given read/write masks
+    // Fails if the parameter writes to a resource that another parameter of the same system already accesses (or the
+    // other way around), since fetching both guards would deadlock
+    fn access(
+        resources: &mut ResourceIds,
+        reads: &mut ResourceMask,
+        writes: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError>;
+
+    // Fetch the parameter from the world right before the system gets called
+    fn fetch(world: &World) -> Result<Self::Item<'_>, SystemParamError>;
+}
+
+impl<R: Resource> SystemParam for Read<'_, R> {
+    type Item<'w> = Read<'w, R>;
+
+    fn access(
+        resources: &mut ResourceIds,
+        reads: &mut ResourceMask,
+        writes: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError> {
+        let mask = resources.mask::<R>()?;
+        if writes.intersects(&mask) {
+            return Err(ResourceMaskError::ConflictingAccess(type_name::<R>()));
+        }
+
+        *reads |= mask;
+        Ok(())
+    }
+
+    fn fetch(world: &World) -> Result<Self::Item<'_>, SystemParamError> {
+        Ok(world.get::<R>()?)
+    }
+}
+
+impl<R: Resource> SystemParam for Write<'_, R> {
+    type Item<'w> = Write<'w, R>;
+
+    fn access(
+        resources: &mut ResourceIds,
+        reads: &mut ResourceMask,
+        writes: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError> {
+        let mask = resources.mask::<R>()?;
+        if reads.intersects(&mask) {
+            return Err(ResourceMaskError::ConflictingAccess(type_name::<R>()));
+        }
+
+        *reads |= mask;
+        *writes |= mask;
+        Ok(())
+    }
+
+    fn fetch(world: &World) -> Result<Self::Item<'_>, SystemParamError> {
+        Ok(world.get_mut::<R>()?)
+    }
+}
+
+// Optional parameters resolve to None when the resource is not present in the world
+impl<P: SystemParam> SystemParam for Option<P> {
+    type Item<'w> = Option<P::Item<'w>>;
+
+    fn access(
+        resources: &mut ResourceIds,
+        reads: &mut ResourceMask,
+        writes: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError> {
+        P::access(resources, reads, writes)
+    }
+
+    fn fetch(world: &World) -> Result<Self::Item<'_>, SystemParamError> {
+        match P::fetch(world) {
+            Ok(item) => Ok(Some(item)),
+            Err(err) if err.not_present() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// A function whose arguments are all system parameters. Marker is only used to tell the implementations apart
+pub trait SystemFunction<Marker>: Send + Sync + 'static {
+    // Add the accesses of all the parameters to the given read/write masks
+    fn access(
+        resources: &mut ResourceIds,
+        reads: &mut ResourceMask,
+        writes: &mut ResourceMask,
+    ) -> Result<(), ResourceMaskError>;
+
+    // Fetch all the parameters and call the function
+    fn run(&mut self, world: &World) -> Result<(), SystemError>;
+}
+
+macro_rules! impl_system_function {
+    ($($param:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::too_many_arguments)]
+        impl<Out, Func, $($param: SystemParam),*> SystemFunction<fn($($param),*) -> Out> for Func
+        where
+            Out: SystemOutput,
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut($($param),*) -> Out + FnMut($($param::Item<'_>),*) -> Out,
+        {
+            fn access(
+                resources: &mut ResourceIds,
+                reads: &mut ResourceMask,
+                writes: &mut ResourceMask,
+            ) -> Result<(), ResourceMaskError> {
+                $($param::access(resources, reads, writes)?;)*
+                Ok(())
+            }
+
+            fn run(&mut self, world: &World) -> Result<(), SystemError> {
+                // Needed so the compiler picks the FnMut impl that takes the fetched items
+                fn call<Out, $($param),*>(mut func: impl FnMut($($param),*) -> Out, $($param: $param),*) -> Out {
+                    func($($param),*)
+                }
+
+                $(let $param = $param::fetch(world)?;)*
+                call(self, $($param),*).into_result()
+            }
+        }
+    };
+}
+
+impl_system_function!();
+impl_system_function!(A);
+impl_system_function!(A, B);
+impl_system_function!(A, B, C);
+impl_system_function!(A, B, C, D);
+impl_system_function!(A, B, C, D, E);
+impl_system_function!(A, B, C, D, E, F);
+impl_system_function!(A, B, C, D, E, F, G);
+impl_system_function!(A, B, C, D, E, F, G, H);
@@ -1,37 +1,12 @@
 use ahash::AHashMap;
-use parking_lot::Mutex;
-use std::{
-    any::{Any, TypeId},
-    sync::LazyLock,
-};
+use std::any::{type_name, Any, TypeId};
 
-pub type ResourceMask = u64;
+use crate::{ResourceMask, ResourceMaskError};
 
 pub trait Resource: Any + 'static + Sync + Send {
-    fn mask() -> ResourceMask
-    where
-        Self: Sized,
-    {
-        // Check if we need to register
-        let id = TypeId::of::<Self>();
-        if REGISTERED.lock().contains_key(&id) {
-            // Read normally
-            let locked = REGISTERED.lock();
-            *locked.get(&id).unwrap()
-        } else {
-            // Register the component
-            let mut locked = REGISTERED.lock();
-            let mut bit = NEXT.lock();
-
-            // Le bitshifting
-            let copy = *bit;
-            locked.insert(TypeId::of::<Self>(), copy);
-            *bit = copy.checked_shl(1).unwrap();
-            copy
-        }
-    }
     fn as_any_ref(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 impl<T: Any + Sync + Send + 'static> Resource for T {
     fn as_any_ref(&self) -> &dyn Any {
@@ -41,8 +16,61 @@ impl<T: Any + Sync + Send + 'static> Resource for T {
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
 }
 
-static NEXT: LazyLock<Mutex<u64>> = LazyLock::new(|| Mutex::new(1));
-static REGISTERED: LazyLock<Mutex<AHashMap<TypeId, u64>>> =
-    LazyLock::new(|| Mutex::new(AHashMap::default()));
+// Allocates a unique bit for every resource type that a registry touches
+// Each registry has its own table, so independent schedules don't share the same bit space
+#[derive(Default, Clone, Debug)]
+pub struct ResourceIds {
+    bits: AHashMap<TypeId, usize>,
+    names: Vec<&'static str>,
+}
+
+impl ResourceIds {
+    // Get the mask of a resource, registering it if needed
+    pub fn mask<R: Resource>(&mut self) -> Result<ResourceMask, ResourceMaskError> {
+        let id = TypeId::of::<R>();
+        if let Some(bit) = self.bits.get(&id) {
+            return Ok(ResourceMask::from_bit(*bit).unwrap());
+        }
+
+        // Register the resource using the next free bit
+        let bit = self.names.len();
+        let mask = ResourceMask::from_bit(bit).ok_or(ResourceMaskError::LimitReached(
+            type_name::<R>(),
+            ResourceMask::BITS,
+        ))?;
+        self.bits.insert(id, bit);
+        self.names.push(type_name::<R>());
+        Ok(mask)
+    }
+
+    // Get the mask of a resource only if it was already registered
+    pub fn get<R: Resource>(&self) -> Option<ResourceMask> {
+        let bit = self.bits.get(&TypeId::of::<R>())?;
+        ResourceMask::from_bit(*bit)
+    }
+
+    // Get the type name of the resource that owns the given bit
+    pub fn name(&self, bit: usize) -> Option<&'static str> {
+        self.names.get(bit).copied()
+    }
+
+    // Get the type names of all the resources within a mask
+    pub fn names(&self, mask: &ResourceMask) -> Vec<&'static str> {
+        mask.bits().filter_map(|bit| self.name(bit)).collect()
+    }
+
+    // Number of registered resource types
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{stage::StageId, world::World};
+use std::marker::PhantomData;
+
+use crate::{
+    stage::{SetId, StageId},
+    world::World,
+};
 
 // A rule that depicts the arrangement and the location of the stages relative to other stages
 #[derive(Clone, Debug, Hash)]
@@ -9,12 +14,49 @@ pub enum InjectionRule {
     // do note that in some cases where the threads are all saturated with tasks,
     // the registry will sort fine even though the underlying tasks will NOT run in parallel
     Parallel(StageId),
+
+    // Set rules expand into Before/After rules for every member of the set when sorting
+    BeforeSet(SetId),
+    AfterSet(SetId),
+}
+
+// Describes where an edge of the rule graph came from
+#[derive(Clone, Debug)]
+pub enum RuleOrigin {
+    // Edge between two anchors (the built-in user/post_user anchors or the anchors of a phase)
+    Anchor,
+
+    // Edge created by a rule that the given stage declared
+    Rule(StageId, InjectionRule),
 }
 
 pub fn user(_: &World) {}
 
 pub fn post_user(_: &World) {}
 
+// Anchors that delimit a user-defined phase. Only their type names matter
+struct PhaseStart<L>(PhantomData<L>);
+struct PhaseEnd<L>(PhantomData<L>);
+
+// A user-defined phase. Systems placed into the phase execute between its start and end anchors,
+// and phases execute one after the other in the order they were added to the registry
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Phase {
+    pub set: SetId,
+    pub start: StageId,
+    pub end: StageId,
+}
+
+impl Phase {
+    pub(crate) fn of<L: 'static>(label: &L) -> Self {
+        Self {
+            set: SetId::of(label),
+            start: StageId::of(&PhaseStart::<L>(PhantomData)),
+            end: StageId::of(&PhaseEnd::<L>(PhantomData)),
+        }
+    }
+}
+
 // Create the default rules for a default node
 pub(super) fn default_rules() -> Vec<InjectionRule> {
     let after = InjectionRule::After(StageId::of(&user));
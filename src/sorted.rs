@@ -1,53 +1,110 @@
-use std::sync::Arc;
+use std::{cmp::Reverse, sync::Arc, time::Duration};
 
 use ahash::AHashMap;
 use ascii_table::AsciiTable;
+use petgraph::Graph;
 
-use crate::{Dispatcher, Internal, StageId, World};
+use crate::{
+    DispatchSettings, Dispatcher, Executor, FrameStats, GroupingStrategy, Internal, PanicPolicy,
+    Phase, ResourceIds, ResourceMask, RuleOrigin, StageId, World,
+};
 
 pub struct DispatchBuilder {
     pub(crate) execution_matrix_cm: Vec<Vec<StageId>>,
+    pub(crate) graph: Graph<StageId, RuleOrigin>,
+    pub(crate) parallel: Vec<Vec<StageId>>,
+    pub(crate) masks: AHashMap<StageId, (ResourceMask, ResourceMask)>,
     pub(crate) systems: AHashMap<StageId, Internal>,
-    pub(crate) per_thread: Vec<Vec<Option<Internal>>>,
+    pub(crate) resources: Arc<ResourceIds>,
+    pub(crate) phases: Vec<Phase>,
+    pub(crate) grouping: Option<Arc<dyn GroupingStrategy>>,
+    pub(crate) per_thread: Vec<Vec<Vec<Internal>>>,
+
+    // Systems that must execute on the thread that calls dispatch (one entry per group)
+    pub(crate) main: Vec<Vec<Internal>>,
+
+    // Estimated wall time of a whole dispatch, computed while balancing
+    pub(crate) makespan: Option<Duration>,
     pub(crate) balanced_thread_count: usize,
+    pub(crate) settings: DispatchSettings,
 }
 
 impl DispatchBuilder {
+    // Assign the systems of each group to the worker threads according to their costs
+    // The groups themselves stay as the grouping strategy made them, so costs never move a system to another group
     pub fn balance(&mut self, thread_count: Option<usize>) {
         let thread_count = thread_count.unwrap_or_else(|| num_cpus::get() - 1).max(1);
 
-        // Handle thread task overflow here (basically leak extra tasks to a new group, repeat until done)
-        // I know this is really ugly. Will fix later
-        let execution_matrix_cm = &mut self.execution_matrix_cm;
-        while execution_matrix_cm.iter().any(|x| x.len() > thread_count) {
-            let group_index_extra = execution_matrix_cm
+        // Systems without a cost are assumed to be as expensive as the average system that has one
+        let systems = &self.systems;
+        let known = systems.values().filter_map(|x| x.cost).collect::<Vec<_>>();
+        let fallback = known.iter().sum::<Duration>() / (known.len().max(1) as u32);
+        let cost = |stage: &StageId| systems.get(stage).and_then(|x| x.cost).unwrap_or(fallback);
+
+        // Pack the worker systems of each group onto the threads using LPT (longest processing time first)
+        // Most expensive systems get placed first, each one on the thread that has the least work within the group,
+        // so a thread can execute multiple cheap systems while another one executes a single expensive system
+        let mut layout = Vec::<Vec<Vec<StageId>>>::with_capacity(self.execution_matrix_cm.len());
+        let mut makespan = Duration::ZERO;
+        for group in self.execution_matrix_cm.iter() {
+            let (main, mut workers): (Vec<_>, Vec<_>) = group
                 .iter()
-                .position(|x| x.len() > thread_count)
-                .unwrap();
-            log::debug!("Goup index: {group_index_extra}");
-            let extras = execution_matrix_cm[group_index_extra]
-                .drain(thread_count..)
-                .collect::<Vec<_>>();
-
-            if extras.is_empty() {
-                panic!();
+                .copied()
+                .partition(|x| systems.get(x).is_some_and(|x| x.main_thread));
+            workers.sort_by_key(|x| Reverse(cost(x)));
+
+            let mut threads = vec![(Duration::ZERO, Vec::<StageId>::new()); thread_count];
+            for stage in workers {
+                let (load, stages) = threads
+                    .iter_mut()
+                    .min_by_key(|(load, stages)| (*load, stages.len()))
+                    .unwrap();
+                *load += cost(&stage);
+                stages.push(stage);
             }
 
-            log::debug!("Extra count: {}", extras.len());
-            execution_matrix_cm.insert(group_index_extra + 1, extras);
+            // Each group takes as long as its busiest worker thread, or all of its main thread systems one after another
+            let main = main.iter().map(cost).sum::<Duration>();
+            let workers = threads
+                .iter()
+                .map(|(load, _)| *load)
+                .max()
+                .unwrap_or_default();
+            makespan += workers.max(main);
+            layout.push(threads.into_iter().map(|(_, stages)| stages).collect());
         }
+        log::debug!("Estimated makespan: {makespan:?}");
 
-        let per_thread = row_major(
+        let (per_thread, main) = row_major(
             thread_count,
-            execution_matrix_cm,
+            &layout,
+            &self.execution_matrix_cm,
             std::mem::take(&mut self.systems),
         );
         self.per_thread = per_thread;
+        self.main = main;
+        self.makespan = Some(makespan);
         self.balanced_thread_count = thread_count;
     }
 
+    // Use the average wall times that a profiler measured as the costs of the systems (see InjectionOrder::cost)
+    // Systems that never executed keep their declared cost. This must be called before balancing to have any effect
+    pub fn learn(&mut self, stats: &FrameStats) {
+        for (stage, timing) in stats.systems.iter() {
+            if let Some(internal) = self.systems.get_mut(stage).filter(|_| timing.samples > 0) {
+                internal.cost = Some(timing.avg);
+            }
+        }
+    }
+
+    // Get the estimated wall time of a whole dispatch according to the costs of the systems
+    // Returns None if the builder was not balanced yet
+    pub fn makespan(&self) -> Option<Duration> {
+        self.makespan
+    }
+
     pub fn build(mut self, world: Arc<World>, thread_count: Option<usize>) -> Dispatcher {
-        if self.per_thread.is_empty() {
+        if self.balanced_thread_count == 0 {
             self.balance(thread_count);
         }
 
@@ -59,55 +116,122 @@ impl DispatchBuilder {
         }
 
         let mut ascii_table = AsciiTable::default();
-        for (i, execs) in self.execution_matrix_cm.iter().enumerate() {
+        for i in 0..self.execution_matrix_cm.len() {
             ascii_table.column(i + 1).set_header(format!("{i}"));
 
-            for j in 0..thread_count {
-                if let Some(x) = execs.get(j) {
-                    let name = &x.name.split("::").last().unwrap();
-                    data[j].push(name.to_string());
+            for (row, column) in data.iter_mut().zip(self.per_thread.iter()) {
+                let names = column[i]
+                    .iter()
+                    .map(|x| x.stage.name.split("::").last().unwrap())
+                    .collect::<Vec<_>>();
+                if names.is_empty() {
+                    row.push("__".to_string());
                 } else {
-                    data[j].push("__".to_string());
+                    row.push(names.join(", "));
                 }
             }
         }
         log::debug!("\n{}", ascii_table.format(data));
 
-        Dispatcher::build(self.per_thread, world)
+        let mut dispatcher = Dispatcher::build(
+            self.per_thread,
+            self.main,
+            &self.graph,
+            world,
+            self.resources,
+            self.phases,
+            self.settings,
+        );
+        dispatcher.grouping = self.grouping;
+        dispatcher
+    }
+
+    // Record every get/get_mut that the systems attempt (including denied ones) so we can compare
+    // them with the declared accesses through Dispatcher::audit
+    pub fn audit(&mut self, enabled: bool) {
+        self.settings.audit = enabled;
+    }
+
+    // Measure the wall time of each system, the idle time of each thread and the total frame time
+    // The dispatcher keeps the last "window" dispatches around, which can be read through Dispatcher::stats
+    pub fn profile(&mut self, window: usize) {
+        self.settings.profile = Some(window);
+    }
+
+    // Set what the dispatcher should do when a system panics (resumes the panic on the main thread by default)
+    pub fn panic_policy(&mut self, policy: PanicPolicy) {
+        self.settings.panic = policy;
+    }
+
+    // Choose how the dispatcher executes the systems (uses fixed groups with barriers in between by default)
+    pub fn executor(&mut self, executor: Executor) {
+        self.settings.executor = executor;
+    }
+
+    // Get the resource ids that the registry allocated
+    pub fn resources(&self) -> &ResourceIds {
+        &self.resources
     }
 
     pub fn group(&self, group: usize) -> Option<&Vec<StageId>> {
         self.execution_matrix_cm.get(group)
     }
 
+    // Get the first system that a worker thread executes within a group (see DispatchBuilder::stages_at)
+    // Returns None if the builder was not balanced yet, or if the thread executes nothing within the group
     pub fn stage_at(&self, group: usize, thread: usize) -> Option<StageId> {
-        let group = self.execution_matrix_cm.get(group)?;
-        group.get(thread).copied()
+        let systems = self.per_thread.get(thread)?.get(group)?;
+        systems.first().map(|x| x.stage)
+    }
+
+    // Get the systems that a worker thread executes within a group, in execution order
+    // Returns None if the builder was not balanced yet, or if there is no such group or thread
+    pub fn stages_at(&self, group: usize, thread: usize) -> Option<Vec<StageId>> {
+        let systems = self.per_thread.get(thread)?.get(group)?;
+        Some(systems.iter().map(|x| x.stage).collect())
     }
 }
 
 fn row_major(
     thread_count: usize,
-    execution_matrix_cm: &Vec<Vec<StageId>>,
+    layout: &[Vec<Vec<StageId>>],
+    execution_matrix_cm: &[Vec<StageId>],
     mut systems: AHashMap<StageId, Internal>,
-) -> Vec<Vec<Option<Internal>>> {
+) -> (Vec<Vec<Vec<Internal>>>, Vec<Vec<Internal>>) {
     // must convert the column major data to row major so each thread has to worry about its own data only
-    let mut per_thread = Vec::<Vec<Option<Internal>>>::default();
+    let mut per_thread = Vec::<Vec<Vec<Internal>>>::default();
     for _ in 0..thread_count {
         per_thread.push(Vec::new());
     }
 
-    for parallel in execution_matrix_cm {
-        for i in 0..thread_count {
-            let internal = parallel.get(i).map(|i| systems.remove(i).unwrap());
-            per_thread[i].push(internal);
+    for threads in layout {
+        for (column, stages) in per_thread.iter_mut().zip(threads) {
+            column.push(stages.iter().map(|i| systems.remove(i).unwrap()).collect());
         }
     }
 
-    per_thread.retain(|x| x.iter().any(|x| x.is_some()));
-    let max = per_thread.iter().map(|x| x.len()).max().unwrap();
-    for x in per_thread.iter_mut() {
-        x.resize_with(max, || None);
+    // main thread systems are kept separately since they don't execute on the worker threads
+    let mut main = execution_matrix_cm
+        .iter()
+        .map(|parallel| {
+            parallel
+                .iter()
+                .filter_map(|i| systems.remove(i))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Number the systems group by group, following the threads and then the main thread, so commands get applied
+    // in the same order no matter which thread executes which system
+    let mut order = 0;
+    for group in 0..main.len() {
+        let workers = per_thread.iter_mut().flat_map(|x| x[group].iter_mut());
+        for internal in workers.chain(main[group].iter_mut()) {
+            internal.order = order;
+            order += 1;
+        }
     }
-    per_thread
+
+    // Empty columns are kept around, so the dispatcher spawns every requested thread and rebuilding can make use of them
+    (per_thread, main)
 }
@@ -1,9 +1,42 @@
 use std::{
-    any::{type_name, TypeId},
+    any::{type_name, Any, TypeId},
     fmt::Debug,
 };
 
-use crate::world::World;
+use crate::{SystemError, World};
+
+// Boxed system as it is stored within the registry and executed by the dispatcher
+pub(crate) type BoxedSystem = Box<dyn FnMut(&World) -> Result<(), SystemError> + Sync + Send>;
+
+// Boxed system that gets mutable access to the whole world (see Registry::insert_exclusive)
+pub(crate) type BoxedExclusiveSystem =
+    Box<dyn FnMut(&mut World) -> Result<(), SystemError> + Sync + Send>;
+
+// Either kind of boxed system
+pub(crate) enum Callback {
+    Shared(BoxedSystem),
+    Exclusive(BoxedExclusiveSystem),
+}
+
+// Boxed run condition that decides if a system should execute during the current dispatch
+pub(crate) type BoxedCondition = Box<dyn FnMut(&World) -> bool + Sync + Send>;
+
+// Return value of a system. Systems can either return nothing or a Result<(), E>
+pub trait SystemOutput: 'static {
+    fn into_result(self) -> Result<(), SystemError>;
+}
+
+impl SystemOutput for () {
+    fn into_result(self) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl<E: Into<SystemError> + 'static> SystemOutput for Result<(), E> {
+    fn into_result(self) -> Result<(), SystemError> {
+        self.map_err(Into::into)
+    }
+}
 
 #[derive(Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub struct StageId {
@@ -17,11 +50,48 @@ impl Debug for StageId {
     }
 }
 
+// Label of a user-defined set of systems. Sets are identified by the type of their label
+// (for example a unit struct such as "struct Physics;"), and systems can be part of multiple sets
+#[derive(Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
+pub struct SetId {
+    pub name: &'static str,
+    pub id: TypeId,
+}
+
+impl Debug for SetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("set \"{}\"", &self.name))
+    }
+}
+
+impl SetId {
+    pub fn of<L: 'static>(_: &L) -> Self {
+        Self {
+            name: type_name::<L>(),
+            id: TypeId::of::<L>(),
+        }
+    }
+}
+
 impl StageId {
-    pub fn of<S: FnOnce(&World) + 'static>(_: &S) -> Self {
+    // Stages are identified by the type of their system, so both plain functions and typed systems work here
+    // Passing a StageId returns it as is, so rules can also target named instances
+    pub fn of<S: 'static>(system: &S) -> Self {
+        if let Some(stage) = (system as &dyn Any).downcast_ref::<StageId>() {
+            return *stage;
+        }
+
         Self {
             name: type_name::<S>(),
             id: TypeId::of::<S>(),
         }
     }
+
+    // Stage of a named instance of a system. Instances of the same system with different names are different stages
+    pub fn named<S: 'static>(_: &S, name: &'static str) -> Self {
+        Self {
+            name,
+            id: TypeId::of::<S>(),
+        }
+    }
 }
@@ -0,0 +1,147 @@
+use std::{collections::VecDeque, time::Duration};
+
+use ahash::AHashMap;
+
+use crate::StageId;
+
+// Min/avg/max of a measured duration over the rolling window of the profiler
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub samples: usize,
+}
+
+// Rolling window of duration samples
+#[derive(Clone, Debug)]
+pub(crate) struct Samples {
+    window: usize,
+    values: VecDeque<Duration>,
+}
+
+impl Samples {
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            values: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: Duration) {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub(crate) fn timing(&self) -> Timing {
+        let samples = self.values.len();
+        if samples == 0 {
+            return Timing::default();
+        }
+
+        let total = self.values.iter().sum::<Duration>();
+        Timing {
+            min: *self.values.iter().min().unwrap(),
+            avg: total / samples as u32,
+            max: *self.values.iter().max().unwrap(),
+            samples,
+        }
+    }
+}
+
+// Timings that a single dispatcher thread measured during a single dispatch
+#[derive(Default)]
+pub(crate) struct ThreadTimings {
+    pub systems: Vec<(StageId, Duration)>,
+    pub idle: Vec<(usize, Duration)>,
+}
+
+// Collects the timings of all the dispatcher threads over multiple dispatches
+pub(crate) struct Profiler {
+    window: usize,
+    frame: Samples,
+    systems: AHashMap<StageId, Samples>,
+    idle: Vec<Vec<Samples>>,
+}
+
+impl Profiler {
+    pub(crate) fn new(window: usize, threads: usize, groups: usize) -> Self {
+        Self {
+            window,
+            frame: Samples::new(window),
+            systems: AHashMap::default(),
+            idle: vec![vec![Samples::new(window); groups]; threads],
+        }
+    }
+
+    pub(crate) fn record_thread(&mut self, thread: usize, timings: ThreadTimings) {
+        for (stage, elapsed) in timings.systems {
+            let window = self.window;
+            self.systems
+                .entry(stage)
+                .or_insert_with(|| Samples::new(window))
+                .push(elapsed);
+        }
+
+        for (group, idle) in timings.idle {
+            self.idle[thread][group].push(idle);
+        }
+    }
+
+    pub(crate) fn record_frame(&mut self, elapsed: Duration) {
+        self.frame.push(elapsed);
+    }
+
+    pub(crate) fn stats(&self) -> FrameStats {
+        let mut systems = self
+            .systems
+            .iter()
+            .map(|(stage, samples)| (*stage, samples.timing()))
+            .collect::<Vec<_>>();
+        systems.sort_by_key(|x| x.0);
+
+        FrameStats {
+            frame: self.frame.timing(),
+            systems,
+            idle: self
+                .idle
+                .iter()
+                .map(|groups| groups.iter().map(Samples::timing).collect())
+                .collect(),
+        }
+    }
+}
+
+// Statistics of the last few dispatches, as measured by the profiler
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    // Total time spent within Dispatcher::dispatch
+    pub frame: Timing,
+
+    // Wall time of each system
+    pub systems: Vec<(StageId, Timing)>,
+
+    // Time that each thread spent waiting for the other threads, indexed by thread then group
+    // With the work-stealing executor, this is the time a thread slept until a task became ready. It counts towards
+    // the group of the task that the thread executed next
+    pub idle: Vec<Vec<Timing>>,
+}
+
+impl FrameStats {
+    // Get the timing of a specific system
+    pub fn system(&self, stage: StageId) -> Option<Timing> {
+        self.systems.iter().find(|x| x.0 == stage).map(|x| x.1)
+    }
+
+    // Get the time a thread spent waiting at the barriers of a group
+    pub fn idle(&self, thread: usize, group: usize) -> Option<Timing> {
+        self.idle.get(thread)?.get(group).copied()
+    }
+
+    // Get the system with the highest average wall time, which is most likely the one stalling its group
+    pub fn slowest(&self) -> Option<(StageId, Timing)> {
+        self.systems.iter().max_by_key(|x| x.1.avg).copied()
+    }
+}
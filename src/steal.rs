@@ -0,0 +1,214 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use ahash::AHashMap;
+use parking_lot::{Condvar, Mutex};
+use petgraph::{visit::Dfs, Graph};
+
+use crate::{Internal, RuleOrigin, StageId};
+
+// Where the system of a task is stored within the dispatcher
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Location {
+    // Column of a worker thread and group within that column
+    Worker(usize, usize),
+
+    // Group of the main thread systems
+    Main(usize),
+}
+
+// A single system as seen by the work-stealing executor
+pub(crate) struct Task {
+    pub stage: StageId,
+    pub location: Location,
+    pub group: usize,
+
+    // Queue that the task gets pushed to once it is ready (the main thread has the last queue)
+    pub home: usize,
+    dependencies: Vec<usize>,
+    dependents: Vec<usize>,
+}
+
+// Dependencies between all the systems of a dispatcher, alongside the ready queues of the threads
+// A system depends on every system that comes before it through the rules and on every system of an earlier group
+// that it conflicts with, so a system that is ready never conflicts with the systems that are currently executing
+pub(crate) struct TaskGraph {
+    pub tasks: Vec<Task>,
+    remaining: Vec<AtomicUsize>,
+    pending: AtomicUsize,
+    queues: Vec<Mutex<VecDeque<usize>>>,
+
+    // Threads that ran out of tasks sleep on this until a task gets queued or the segment finishes
+    sleep: Mutex<()>,
+    wake: Condvar,
+}
+
+impl TaskGraph {
+    pub(crate) fn new(
+        graph: &Graph<StageId, RuleOrigin>,
+        per_thread: &[Vec<Vec<Internal>>],
+        main: &[Vec<Internal>],
+    ) -> Self {
+        let threads = per_thread.len();
+        let workers = per_thread.iter().enumerate().flat_map(|(thread, column)| {
+            column.iter().enumerate().flat_map(move |(group, systems)| {
+                systems
+                    .iter()
+                    .map(move |x| (x, Location::Worker(thread, group), group, thread))
+            })
+        });
+        let main = main.iter().enumerate().flat_map(|(group, systems)| {
+            systems
+                .iter()
+                .map(move |x| (x, Location::Main(group), group, threads))
+        });
+        let systems = workers.chain(main).collect::<Vec<_>>();
+
+        let indices = systems
+            .iter()
+            .enumerate()
+            .map(|(i, (internal, ..))| (internal.stage, i))
+            .collect::<AHashMap<_, _>>();
+        let nodes = graph
+            .node_indices()
+            .map(|node| (graph[node], node))
+            .collect::<AHashMap<_, _>>();
+
+        let mut tasks = systems
+            .iter()
+            .map(|(internal, location, group, home)| Task {
+                stage: internal.stage,
+                location: *location,
+                group: *group,
+                home: *home,
+                dependencies: Vec::new(),
+                dependents: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        for (i, (internal, _, group, _)) in systems.iter().enumerate() {
+            let mut dependents = Vec::<usize>::new();
+
+            // Systems that come after this one through the rules (anchors and phases included)
+            if let Some(node) = nodes.get(&internal.stage) {
+                let mut dfs = Dfs::new(graph, *node);
+                while let Some(next) = dfs.next(graph) {
+                    dependents.extend(indices.get(&graph[next]).filter(|j| **j != i));
+                }
+            }
+
+            // Systems of later groups that access the same resources
+            for (j, (other, _, other_group, _)) in systems.iter().enumerate() {
+                let conflict = internal.writes.intersects(&(other.reads | other.writes))
+                    || other.writes.intersects(&internal.reads);
+                if *other_group > *group && conflict {
+                    dependents.push(j);
+                }
+            }
+
+            dependents.retain(|j| systems[*j].2 > *group);
+            dependents.sort_unstable();
+            dependents.dedup();
+            for j in dependents.iter() {
+                tasks[*j].dependencies.push(i);
+            }
+            tasks[i].dependents = dependents;
+        }
+
+        Self {
+            remaining: tasks.iter().map(|_| AtomicUsize::new(0)).collect(),
+            tasks,
+            pending: AtomicUsize::new(0),
+            queues: (0..=threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            sleep: Mutex::new(()),
+            wake: Condvar::new(),
+        }
+    }
+
+    // Reset the dependency counters of the tasks within the groups of a segment and queue the ones that are ready
+    // Dependencies on groups outside of the segment already finished (or will execute later on)
+    pub(crate) fn prepare(&self, segment: &Range<usize>) {
+        let mut pending = 0;
+        for (i, task) in self.tasks.iter().enumerate() {
+            if !segment.contains(&task.group) {
+                continue;
+            }
+
+            let count = task
+                .dependencies
+                .iter()
+                .filter(|j| segment.contains(&self.tasks[**j].group))
+                .count();
+            self.remaining[i].store(count, Ordering::Relaxed);
+            pending += 1;
+
+            if count == 0 {
+                self.queues[task.home].lock().push_back(i);
+            }
+        }
+        self.pending.store(pending, Ordering::Release);
+        self.notify();
+    }
+
+    // Check if all the tasks of the current segment finished
+    pub(crate) fn done(&self) -> bool {
+        self.pending.load(Ordering::Acquire) == 0
+    }
+
+    // Get the next ready task for a thread. Threads take tasks from their own queue first, then steal from the other
+    // worker threads. Only the main thread (the last thread) takes tasks from the main thread queue
+    pub(crate) fn next(&self, thread: usize) -> Option<usize> {
+        let main = self.queues.len() - 1;
+        if let Some(task) = self.queues[thread].lock().pop_front() {
+            return Some(task);
+        }
+
+        (0..main)
+            .map(|x| (thread + x + 1) % main)
+            .find_map(|x| self.queues[x].lock().pop_back())
+    }
+
+    // Mark a task as finished, queueing its dependents that became ready
+    pub(crate) fn complete(&self, task: usize, segment: &Range<usize>) {
+        let mut queued = false;
+        for &j in self.tasks[task].dependents.iter() {
+            let dependent = &self.tasks[j];
+            if segment.contains(&dependent.group)
+                && self.remaining[j].fetch_sub(1, Ordering::AcqRel) == 1
+            {
+                self.queues[dependent.home].lock().push_back(j);
+                queued = true;
+            }
+        }
+
+        let finished = self.pending.fetch_sub(1, Ordering::AcqRel) == 1;
+        if queued || finished {
+            self.notify();
+        }
+    }
+
+    // Put a thread to sleep until there is a task that it can take, or until all the tasks of the segment finished
+    pub(crate) fn wait(&self, thread: usize) {
+        let mut sleep = self.sleep.lock();
+        while !self.done() && !self.available(thread) {
+            self.wake.wait(&mut sleep);
+        }
+    }
+
+    // Check if there is a queued task that the thread can take (see TaskGraph::next)
+    fn available(&self, thread: usize) -> bool {
+        let main = self.queues.len() - 1;
+        !self.queues[thread].lock().is_empty()
+            || self.queues[..main].iter().any(|x| !x.lock().is_empty())
+    }
+
+    // Wake up all the sleeping threads. Taking the lock first makes sure that a thread can't miss the wake up
+    // in between checking for tasks and going to sleep
+    fn notify(&self) {
+        let _sleep = self.sleep.lock();
+        self.wake.notify_all();
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+// A single slice on the timeline of a thread
+#[derive(Clone, Debug)]
+pub(crate) struct TraceSlice {
+    pub name: String,
+    pub category: &'static str,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+impl TraceSlice {
+    pub(crate) fn system(name: &'static str, start: Instant, duration: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            category: "system",
+            start,
+            duration,
+        }
+    }
+
+    pub(crate) fn barrier(group: usize, start: Instant, duration: Duration) -> Self {
+        Self {
+            name: format!("barrier (group {group})"),
+            category: "barrier",
+            start,
+            duration,
+        }
+    }
+}
+
+// Records the timelines of the dispatcher threads for a fixed number of dispatches
+pub(crate) struct Tracer {
+    origin: Instant,
+    threads: usize,
+    remaining: usize,
+    frames: usize,
+    slices: Vec<(usize, TraceSlice)>,
+}
+
+impl Tracer {
+    pub(crate) fn new(threads: usize, frames: usize) -> Self {
+        Self {
+            origin: Instant::now(),
+            threads,
+            remaining: frames,
+            frames: 0,
+            slices: Vec::new(),
+        }
+    }
+
+    // Check if we should record the next dispatch
+    pub(crate) fn recording(&self) -> bool {
+        self.remaining > 0
+    }
+
+    pub(crate) fn record_thread(&mut self, thread: usize, slices: Vec<TraceSlice>) {
+        self.slices.extend(slices.into_iter().map(|x| (thread, x)));
+    }
+
+    // Record the whole dispatch on the track of the thread that called dispatch
+    pub(crate) fn record_frame(&mut self, start: Instant, duration: Duration) {
+        let slice = TraceSlice {
+            name: format!("dispatch {}", self.frames),
+            category: "frame",
+            start,
+            duration,
+        };
+        self.slices.push((self.threads, slice));
+        self.frames += 1;
+        self.remaining = self.remaining.saturating_sub(1);
+    }
+
+    // Convert the recorded slices to the Chrome Trace Event Format (JSON)
+    pub(crate) fn json(&self) -> String {
+        let mut events = Vec::<String>::new();
+
+        for tid in 0..=self.threads {
+            let name = if tid == self.threads {
+                "main".to_string()
+            } else {
+                format!("thread-{tid}")
+            };
+
+            events.push(format!(
+                r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{tid},"args":{{"name":"{name}"}}}}"#
+            ));
+        }
+
+        for (tid, slice) in self.slices.iter() {
+            let ts = slice
+                .start
+                .saturating_duration_since(self.origin)
+                .as_nanos() as f64
+                / 1000.0;
+            let dur = slice.duration.as_nanos() as f64 / 1000.0;
+            events.push(format!(
+                r#"{{"name":"{}","cat":"{}","ph":"X","ts":{ts:.3},"dur":{dur:.3},"pid":0,"tid":{tid}}}"#,
+                escape(&slice.name),
+                slice.category,
+            ));
+        }
+
+        let mut json = String::from("{\"traceEvents\":[\n");
+        for (i, event) in events.iter().enumerate() {
+            let separator = if i + 1 < events.len() { "," } else { "" };
+            writeln!(json, "{event}{separator}").unwrap();
+        }
+        json.push_str("],\"displayTimeUnit\":\"ms\"}\n");
+        json
+    }
+}
+
+// Escape a string so it can be used within JSON
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
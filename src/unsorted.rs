@@ -1,38 +1,228 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
 use ahash::AHashMap;
 use petgraph::{
-    graph::NodeIndex,
+    algo::tarjan_scc,
+    graph::{EdgeIndex, NodeIndex},
     visit::{EdgeRef, Topo},
     Graph,
 };
 
 use crate::{
     inject::InjectionOrder,
-    rules::{default_rules, post_user, user, InjectionRule},
-    stage::StageId,
+    rules::{default_rules, post_user, user, InjectionRule, Phase, RuleOrigin},
+    stage::{BoxedCondition, Callback, SetId, StageId, SystemOutput},
     world::World,
-    DispatchBuilder, RegistrySortingError, ResourceMask, StageError,
+    DispatchBuilder, FirstFit, GroupNode, GroupingStrategy, RegistrySortingError, Resource,
+    ResourceIds, ResourceMask, ResourceMaskError, StageError, SystemFunction,
 };
 
 pub(crate) struct Internal {
-    pub(crate) boxed: Box<dyn FnMut(&World) + Sync + Send>,
+    pub(crate) stage: StageId,
+    pub(crate) boxed: Callback,
     pub(crate) rules: Vec<InjectionRule>,
     pub(crate) reads: ResourceMask,
     pub(crate) writes: ResourceMask,
+    pub(crate) mask_error: Option<ResourceMaskError>,
+    pub(crate) enabled: bool,
+    pub(crate) conditions: Vec<BoxedCondition>,
+    pub(crate) sets: Vec<SetId>,
+    pub(crate) main_thread: bool,
+
+    // Estimated wall time of the system, either declared or measured by the profiler (used for balancing)
+    pub(crate) cost: Option<Duration>,
+
+    // Change tick of the last execution of the system
+    pub(crate) last_run: u64,
+
+    // Position of the system within the balanced schedule, unique across all the threads. Orders its commands
+    pub(crate) order: usize,
+}
+
+// Execution groups, rule graph and parallel groups of a sorted registry
+pub(crate) type Plan = (
+    Vec<Vec<StageId>>,
+    Graph<StageId, RuleOrigin>,
+    Vec<Vec<StageId>>,
+);
+
+impl Internal {
+    // Check if the system needs mutable access to the whole world
+    pub(crate) fn exclusive(&self) -> bool {
+        matches!(self.boxed, Callback::Exclusive(_))
+    }
+
+    // Drop all the rules that reference the given stage
+    pub(crate) fn forget(&mut self, stage: StageId) {
+        self.rules.retain(|rule| match rule {
+            InjectionRule::Before(x) | InjectionRule::After(x) | InjectionRule::Parallel(x) => {
+                *x != stage
+            }
+            InjectionRule::BeforeSet(_) | InjectionRule::AfterSet(_) => true,
+        });
+    }
 }
 
 #[derive(Default)]
 pub struct Registry {
     systems: AHashMap<StageId, Internal>,
+    resources: ResourceIds,
+    phases: Vec<Phase>,
+
+    // Strategy that decides which systems execute in parallel (FirstFit if not set)
+    grouping: Option<Arc<dyn GroupingStrategy>>,
 }
 
 impl Registry {
+    pub(crate) fn from_parts(
+        systems: AHashMap<StageId, Internal>,
+        resources: ResourceIds,
+        phases: Vec<Phase>,
+        grouping: Option<Arc<dyn GroupingStrategy>>,
+    ) -> Self {
+        Self {
+            systems,
+            resources,
+            phases,
+            grouping,
+        }
+    }
+
+    // Replace the strategy that decides which systems execute in parallel (see GroupingStrategy)
+    // Dispatchers keep using the strategy of their registry when they get rebuilt
+    pub fn set_grouping(&mut self, strategy: impl GroupingStrategy + 'static) {
+        self.grouping = Some(Arc::new(strategy));
+    }
+
+    pub(crate) fn into_systems(self) -> AHashMap<StageId, Internal> {
+        self.systems
+    }
+
+    // Remove a system from the registry. Rules that other systems had on the removed system get dropped as well
+    // Returns false if there was no such system
+    pub fn remove(&mut self, stage: StageId) -> bool {
+        let removed = self.systems.remove(&stage).is_some();
+        if removed {
+            for internal in self.systems.values_mut() {
+                internal.forget(stage);
+            }
+        }
+        removed
+    }
+
+    // Add a new phase that executes after all the previously added phases. The built-in user phase (where systems
+    // go by default) always executes first. Returns false if the phase was already added
+    pub fn add_phase<L: 'static>(&mut self, label: L) -> bool {
+        let phase = Phase::of(&label);
+        if self.phases.iter().any(|x| x.set == phase.set) {
+            return false;
+        }
+
+        self.phases.push(phase);
+        true
+    }
+
+    // Check if the registry contains a system
+    pub fn contains(&self, stage: StageId) -> bool {
+        self.systems.contains_key(&stage)
+    }
+
+    // Get the mask of a resource within this registry, allocating a new bit if needed
+    pub fn mask<R: Resource>(&mut self) -> Result<ResourceMask, ResourceMaskError> {
+        self.resources.mask::<R>()
+    }
+
+    // Get the resource ids that were allocated by this registry
+    pub fn resources(&self) -> &ResourceIds {
+        &self.resources
+    }
+
     // Add a new system to the registry so we can execute it
-    pub fn insert<S: FnMut(&World) + Sync + Send + 'static>(
+    // Systems can either return nothing or a Result<(), E>, in which case errors get reported by Dispatcher::dispatch
+    pub fn insert<O: SystemOutput, S: FnMut(&World) -> O + Sync + Send + 'static>(
         &mut self,
         system: S,
-    ) -> Result<InjectionOrder, StageError> {
-        let rules = default_rules();
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let stage = StageId::of(&system);
+        self.insert_as(stage, system)
+    }
+
+    // Add a named instance of a system, so the same system can be inserted multiple times (see StageId::named)
+    pub fn insert_named<O: SystemOutput, S: FnMut(&World) -> O + Sync + Send + 'static>(
+        &mut self,
+        name: &'static str,
+        system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let stage = StageId::named(&system, name);
+        self.insert_as(stage, system)
+    }
+
+    fn insert_as<O: SystemOutput, S: FnMut(&World) -> O + Sync + Send + 'static>(
+        &mut self,
+        stage: StageId,
+        mut system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let boxed = Box::new(move |world: &World| system(world).into_result());
+        self.insert_boxed(stage, Callback::Shared(boxed))
+    }
+
+    // Add a system that gets mutable access to the whole world (for save/load, level transitions, bulk swaps...)
+    // Exclusive systems always execute on the thread that calls dispatch, within a group of their own, while no other system executes
+    pub fn insert_exclusive<O: SystemOutput, S: FnMut(&mut World) -> O + Sync + Send + 'static>(
+        &mut self,
+        mut system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let stage = StageId::of(&system);
+        let boxed = Box::new(move |world: &mut World| system(world).into_result());
+        let order = self.insert_boxed(stage, Callback::Exclusive(boxed))?;
+        order.internal.main_thread = true;
+        Ok(order)
+    }
+
+    // Add a new system whose resource accesses are derived from its typed parameters
+    // fn physics(pos: Write<Position>, vel: Read<Velocity>) will write to Position and read from Velocity
+    // This is separate from Registry::insert because a generic SystemFunction bound gives closures no expected
+    // signature, so untyped closures such as registry.insert(|_| {}) would no longer compile
+    // Parameters that conflict with each other (Read<A> and Write<A>) make Registry::sort fail
+    pub fn insert_system<P, S: SystemFunction<P>>(
+        &mut self,
+        system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
         let stage = StageId::of(&system);
+        self.insert_system_as(stage, system)
+    }
+
+    // Add a named instance of a typed system (see Registry::insert_named)
+    pub fn insert_system_named<P, S: SystemFunction<P>>(
+        &mut self,
+        name: &'static str,
+        system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let stage = StageId::named(&system, name);
+        self.insert_system_as(stage, system)
+    }
+
+    fn insert_system_as<P, S: SystemFunction<P>>(
+        &mut self,
+        stage: StageId,
+        mut system: S,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let mut reads = ResourceMask::default();
+        let mut writes = ResourceMask::default();
+        let mask_error = S::access(&mut self.resources, &mut reads, &mut writes).err();
+
+        let boxed = Box::new(move |world: &World| system.run(world));
+        let order = self.insert_boxed(stage, Callback::Shared(boxed))?;
+        order.internal.mask_error = mask_error;
+        Ok(order.reads_mask(reads).writes_mask(writes))
+    }
+
+    fn insert_boxed(
+        &mut self,
+        stage: StageId,
+        boxed: Callback,
+    ) -> Result<InjectionOrder<'_>, StageError> {
+        let rules = default_rules();
 
         if self.systems.contains_key(&stage) {
             return Err(StageError::Overlapping);
@@ -45,14 +235,23 @@ impl Registry {
         self.systems.insert(
             stage,
             Internal {
-                boxed: Box::new(system),
+                stage,
+                boxed,
                 rules,
                 reads: ResourceMask::default(),
                 writes: ResourceMask::default(),
+                mask_error: None,
+                enabled: true,
+                conditions: Vec::new(),
+                sets: Vec::new(),
+                main_thread: false,
+                cost: None,
+                last_run: 0,
+                order: 0,
             },
         );
         let internal = self.systems.get_mut(&stage).unwrap();
-        Ok(InjectionOrder::new(internal))
+        Ok(InjectionOrder::new(internal, &mut self.resources))
     }
 
     // two (three) constraints
@@ -60,7 +259,48 @@ impl Registry {
     // 2) make sure no intersecting RW masks
     // 3) (optional) optimize RW masks to improve concurrency
     pub fn sort(self) -> Result<DispatchBuilder, RegistrySortingError> {
-        let mut graph = Graph::<StageId, ()>::new();
+        let plan = self.plan()?;
+        Ok(self.into_builder(plan))
+    }
+
+    // Create a builder from a plan that was computed by Registry::plan
+    pub(crate) fn into_builder(
+        self,
+        (execution_matrix_cm, graph, parallel): Plan,
+    ) -> DispatchBuilder {
+        let masks = self
+            .systems
+            .iter()
+            .map(|(stage, internal)| (*stage, (internal.reads, internal.writes)))
+            .collect();
+
+        DispatchBuilder {
+            execution_matrix_cm,
+            graph,
+            parallel,
+            masks,
+            systems: self.systems,
+            resources: Arc::new(self.resources),
+            phases: self.phases,
+            grouping: self.grouping,
+            per_thread: Default::default(),
+            main: Default::default(),
+            makespan: None,
+            balanced_thread_count: 0,
+            settings: Default::default(),
+        }
+    }
+
+    // Compute the execution groups, the rule graph and the parallel groups without consuming the registry
+    pub(crate) fn plan(&self) -> Result<Plan, RegistrySortingError> {
+        if let Some((stage, internal)) = self.systems.iter().find(|x| x.1.mask_error.is_some()) {
+            return Err(RegistrySortingError::InvalidResourceMask(
+                *stage,
+                internal.mask_error.unwrap(),
+            ));
+        }
+
+        let mut graph = Graph::<StageId, RuleOrigin>::new();
 
         let mut temp_vec = self.systems.iter().collect::<Vec<_>>();
         temp_vec.sort_by_key(|x| x.0);
@@ -76,10 +316,30 @@ impl Registry {
         let sid = StageId::of(&post_user);
         let post_user = graph.add_node(sid);
         nodes.insert(sid, post_user);
-        graph.add_edge(user, post_user, ());
+        graph.add_edge(user, post_user, RuleOrigin::Anchor);
+
+        // Chain the anchors of the phases after the built-in ones
+        let mut previous = post_user;
+        for phase in self.phases.iter() {
+            let start = graph.add_node(phase.start);
+            let end = graph.add_node(phase.end);
+            nodes.insert(phase.start, start);
+            nodes.insert(phase.end, end);
+            graph.add_edge(previous, start, RuleOrigin::Anchor);
+            graph.add_edge(start, end, RuleOrigin::Anchor);
+            previous = end;
+        }
 
         let mut should_execute_in_parallel = Vec::<Vec<StageId>>::new();
 
+        // Members of each set (sorted by stage like the nodes)
+        let mut sets = AHashMap::<SetId, Vec<StageId>>::default();
+        for (node, internal) in temp_vec.iter() {
+            for set in internal.sets.iter() {
+                sets.entry(*set).or_default().push(**node);
+            }
+        }
+
         for (node, internal) in temp_vec.iter() {
             for rule in internal.rules.iter() {
                 let this = nodes[node];
@@ -87,20 +347,36 @@ impl Registry {
                     InjectionRule::Before(p) => *p,
                     InjectionRule::After(p) => *p,
                     InjectionRule::Parallel(p) => *p,
+
+                    // Expand set rules into edges to every member of the set. Empty (or unknown) sets do nothing
+                    InjectionRule::BeforeSet(set) | InjectionRule::AfterSet(set) => {
+                        let members = sets.get(set).map(Vec::as_slice).unwrap_or_default();
+                        for member in members.iter().filter(|x| *x != *node) {
+                            let origin = RuleOrigin::Rule(**node, rule.clone());
+                            let member = nodes[member];
+                            if let InjectionRule::BeforeSet(_) = rule {
+                                graph.add_edge(this, member, origin);
+                            } else {
+                                graph.add_edge(member, this, origin);
+                            }
+                        }
+                        continue;
+                    }
                 };
                 
                 let reference_node = *nodes
                     .get(&reference)
                     .ok_or(RegistrySortingError::MissingStage(**node, reference))?;
 
+                let origin = RuleOrigin::Rule(**node, rule.clone());
                 match rule {
                     // dir: a -> b.
                     // dir: this -> reference
-                    InjectionRule::Before(_) => { graph.add_edge(this, reference_node, ()); },
+                    InjectionRule::Before(_) => { graph.add_edge(this, reference_node, origin); },
 
                     // dir: a -> b.
                     // dir: reference -> this
-                    InjectionRule::After(_) => { graph.add_edge(reference_node, this, ()); },
+                    InjectionRule::After(_) => { graph.add_edge(reference_node, this, origin); },
 
                     // find a rule group that we can add the stage id into
                     InjectionRule::Parallel(_) => {
@@ -111,163 +387,210 @@ impl Registry {
                             should_execute_in_parallel.push(vec![**node, reference]);
                         }
                     },
+
+                    // already expanded above
+                    InjectionRule::BeforeSet(_) | InjectionRule::AfterSet(_) => unreachable!(),
                 };
             }
         }
 
-        // Groups for each type of resource access
-        // Must correspond to the "depth" of each nodes as we can't mix and match groups from different levels (otherwise it would fuck
-        // with the first requirement of having proper depedency sorting)
-        let mut groups = Vec::<(i32, ResourceMask, ResourceMask, Vec<NodeIndex>)>::default();
-
-        #[derive(Debug, Clone)]
-        enum Testino {
-            Concrete(i32),
-            Ref(NodeIndex),
+        // Cyclic rules would make the topological sort skip nodes, so report them early
+        if let Some(cycle) = find_cycle(&graph) {
+            return Err(RegistrySortingError::CyclicRules(cycle));
         }
 
+        // Depth of each node is the length of the longest path leading to it, so a system always ends up deeper
+        // than every system it depends on. Visiting the nodes in topological order means that all the predecessors
+        // of a node already have their final depth
         let mut path_sorted = Vec::<(NodeIndex, i32)>::default();
-        let mut test = AHashMap::<NodeIndex, Testino>::default();
-        let mut bruh = Topo::new(&graph);
-        while let Some(a) = bruh.next(&graph) {
-            if test.is_empty() {
-                test.insert(a, Testino::Concrete(0));
-            }
+        let mut depths = AHashMap::<NodeIndex, i32>::default();
+        let mut topo = Topo::new(&graph);
+        while let Some(node) = topo.next(&graph) {
+            let depth = graph
+                .neighbors_directed(node, petgraph::Direction::Incoming)
+                .map(|x| depths[&x] + 1)
+                .max()
+                .unwrap_or_default();
+            log::debug!("{:?}, Depth: {depth}", graph.node_weight(node).unwrap());
+            depths.insert(node, depth);
+            path_sorted.push((node, depth));
+        }
 
-            log::debug!(
-                "{:?}, Depth: {:?}",
-                graph.node_weight(a).unwrap(),
-                test.get(&a)
-            );
-            if test.get(&a).is_some() {
-                for x in graph.edges_directed(a, petgraph::Direction::Outgoing) {
-                    let rizz = test
-                        .get(&a)
-                        .map(|x| match x {
-                            Testino::Concrete(u) => Testino::Concrete(u + 1),
-                            Testino::Ref(u) => Testino::Ref(*u),
-                        })
-                        .unwrap_or_else(|| Testino::Ref(a));
-
-                    test.insert(x.target(), rizz);
-                }
+        // Let the grouping strategy decide which systems execute in parallel, then make sure it did not break anything
+        let group_nodes = path_sorted
+            .iter()
+            .filter_map(|&(index, depth)| {
+                let internal = self.systems.get(&graph[index])?;
+                Some(GroupNode {
+                    stage: internal.stage,
+                    depth,
+                    reads: internal.reads,
+                    writes: internal.writes,
+                    exclusive: internal.exclusive(),
+                    main_thread: internal.main_thread,
+                    cost: internal.cost,
+                })
+            })
+            .collect::<Vec<_>>();
+        let execution_matrix_cm = self
+            .grouping
+            .as_deref()
+            .unwrap_or(&FirstFit)
+            .group(&group_nodes);
+        validate_groups(&graph, &group_nodes, &execution_matrix_cm)?;
+        let count = execution_matrix_cm.iter().map(Vec::len).sum::<usize>();
+
+        // Check for parallel rules to make sure we upheld them
+        for a in should_execute_in_parallel.iter() {
+            if execution_matrix_cm
+                .iter()
+                .any(|y| a.iter().all(|j| y.contains(j)))
+            {
+                continue;
             }
 
-            path_sorted.push((a, 0));
+            // Find the first stage that did not end up in the same group as the first one
+            let first = a[0];
+            let group = execution_matrix_cm.iter().find(|y| y.contains(&first));
+            let other = a[1..]
+                .iter()
+                .find(|x| group.is_none_or(|y| !y.contains(x)))
+                .copied()
+                .unwrap_or(first);
+
+            let masks = |stage: &StageId| {
+                self.systems
+                    .get(stage)
+                    .map(|x| (x.reads, x.writes))
+                    .unwrap_or_default()
+            };
+            let ((a_reads, a_writes), (b_reads, b_writes)) = (masks(&first), masks(&other));
+            let conflicts = (a_writes & (b_reads | b_writes)) | (b_writes & (a_reads | a_writes));
+            return Err(RegistrySortingError::UnsatisfiableParallelRules(
+                first,
+                other,
+                self.resources.names(&conflicts),
+            ));
         }
 
-        // oh god it is horrible please make it stop
-        for (key, val) in test.iter() {
-            let (_, depth_to_write) = path_sorted.iter_mut().find(|x| x.0 == *key).unwrap();
-
-            let mut next = val.clone();
-            let mut count = 0;
-            while let Testino::Ref(idx) = next {
-                next = test[&idx].clone();
-                count += 1;
-            }
+        // Every node should have been visited since there aren't any cycles
+        debug_assert_eq!(count, temp_vec.len());
+        Ok((execution_matrix_cm, graph, should_execute_in_parallel))
+    }
+}
 
-            if let Testino::Concrete(k) = next {
-                *depth_to_write = k + count;
-            } else {
-                panic!();
+// Make sure that the groups of a grouping strategy contain every system exactly once, that systems within the same
+// group are compatible and that every system executes after the systems that it depends on
+fn validate_groups(
+    graph: &Graph<StageId, RuleOrigin>,
+    nodes: &[GroupNode],
+    groups: &[Vec<StageId>],
+) -> Result<(), RegistrySortingError> {
+    let invalid =
+        |stage: StageId, reason: &'static str| RegistrySortingError::InvalidGrouping(stage, reason);
+
+    let mut placed = AHashMap::<StageId, usize>::default();
+    for (index, group) in groups.iter().enumerate() {
+        for stage in group.iter() {
+            if !nodes.iter().any(|x| x.stage == *stage) || placed.insert(*stage, index).is_some() {
+                return Err(invalid(
+                    *stage,
+                    "it is unknown or was placed more than once",
+                ));
             }
         }
+    }
 
-        // this will batch the system into their "group" batches where they can execute in parallel with other systems
-        // without overlapping their resource bitmasks
-        for &(index, depth) in path_sorted.iter() {
-            let (stage_id, _) = nodes.iter().find(|x| *x.1 == index).unwrap();
-
-            let Some(internal) = self.systems.get(stage_id) else {
-                continue;
-            };
+    if let Some(node) = nodes.iter().find(|x| !placed.contains_key(&x.stage)) {
+        return Err(invalid(node.stage, "it was never placed"));
+    }
 
-            let node_reads = internal.reads;
-            let node_writes = internal.writes;
-            log::debug!(
-                "System: {}, Depth: {} R: {:#06b}, W: {:#06b}",
-                graph[index].name,
-                depth,
-                node_reads,
-                node_writes
-            );
-
-            // must find group with the following requirements:
-            // 1) same depth as current node depth
-            // 2) non intersecting read/writes
-
-            // TODO: one could even write a custom heuristic function here to split off systems within groups if needed
-            // within a group, there should be shared access to all read resources, but unique access to all write resources
-            let group_index =
-                groups
-                    .iter()
-                    .position(|(group_depth, group_reads, group_writes, _)| {
-                        // check group depth
-                        let depth = *group_depth == depth;
-
-                        // check for ref-mut collisions
-                        let ref_mut_collisions = (node_reads | node_writes) & group_writes == 0
-                            && ((node_writes) & group_reads == 0);
-
-                        // check for mut-mut collisions
-                        let mut_mut_collisions = (node_writes & group_writes) == 0;
-
-                        depth && ref_mut_collisions && mut_mut_collisions
-                    });
-
-            // if the group is missing, add it, otherwise just modify the current group
-            if let Some(group_index) = group_index {
-                let (_, read, writes, nodes) = &mut groups[group_index];
-                *read |= node_reads;
-                *writes |= node_writes;
-                nodes.push(index);
-            } else {
-                groups.push((depth, node_reads, node_writes, vec![index]));
+    for group in groups.iter() {
+        let group = group
+            .iter()
+            .map(|stage| nodes.iter().find(|x| x.stage == *stage).unwrap())
+            .collect::<Vec<_>>();
+        for (i, node) in group.iter().enumerate() {
+            if group[i + 1..].iter().any(|other| !node.compatible(other)) {
+                return Err(invalid(
+                    node.stage,
+                    "it conflicts with another system of its group",
+                ));
             }
         }
+    }
 
-        for (i, (depth, reads, writes, _)) in groups.iter().enumerate() {
-            log::debug!(
-                "Index: {i}, Depth {depth}, R: {:#06b}, W: {:#06b}",
-                *reads,
-                *writes
-            )
+    // Earliest group that each node of the rule graph can execute in (anchors pass the constraints through)
+    let mut earliest = AHashMap::<NodeIndex, usize>::default();
+    let mut topo = Topo::new(graph);
+    while let Some(node) = topo.next(graph) {
+        let minimum = graph
+            .edges_directed(node, petgraph::Direction::Incoming)
+            .map(|edge| match placed.get(&graph[edge.source()]) {
+                Some(group) => group + 1,
+                None => earliest.get(&edge.source()).copied().unwrap_or_default(),
+            })
+            .max()
+            .unwrap_or_default();
+
+        if placed
+            .get(&graph[node])
+            .is_some_and(|group| *group < minimum)
+        {
+            return Err(invalid(
+                graph[node],
+                "it executes before a system that it depends on",
+            ));
         }
+        earliest.insert(node, minimum);
+    }
 
-        // column based table to know what to execute in parallel
-        let mut execution_matrix_cm = Vec::<Vec<StageId>>::default();
-        let mut count = 0;
-        groups.sort_by_key(|(depth, _, _, _)| *depth);
-        for (_, _, _, x) in groups.iter() {
-            let g = x
-                .iter()
-                .map(|a| *graph.node_weight(*a).unwrap())
-                .collect::<Vec<_>>();
-            count += g.len();
-            execution_matrix_cm.push(g);
-        }
+    Ok(())
+}
 
-        // Check for parallel rules to make sure we upheld them
-        let mut fine = true;
-        for a in should_execute_in_parallel {
-            fine &= execution_matrix_cm.iter().any(|y| a.iter().all(|j| y.contains(j)));
-        }
+// Find a cycle within the rule graph (if there is one)
+// Returns the stages of the cycle in order, alongside the origin of the edge that goes to the next stage
+fn find_cycle(graph: &Graph<StageId, RuleOrigin>) -> Option<Vec<(StageId, RuleOrigin)>> {
+    // Stages that reference themselves are the simplest cycles
+    if let Some(edge) = graph.edge_references().find(|x| x.source() == x.target()) {
+        return Some(vec![(graph[edge.source()], edge.weight().clone())]);
+    }
 
-        if !fine {
-            return Err(RegistrySortingError::UnsatisfiableParallelRules);
-        }
+    let component = tarjan_scc(graph).into_iter().find(|x| x.len() > 1)?;
+    let start = component[0];
 
-        // If there are missing nodes then we must have a cylic reference
-        if count < temp_vec.len() {
-            return Err(RegistrySortingError::GraphVisitMissingNodes);
+    // Breadth first search within the component to find the shortest path back to the start
+    let mut parents = AHashMap::<NodeIndex, EdgeIndex>::default();
+    let mut queue = VecDeque::from([start]);
+    'search: while let Some(node) = queue.pop_front() {
+        for edge in graph.edges_directed(node, petgraph::Direction::Outgoing) {
+            let target = edge.target();
+            if !component.contains(&target) || parents.contains_key(&target) {
+                continue;
+            }
+
+            parents.insert(target, edge.id());
+            if target == start {
+                break 'search;
+            }
+            queue.push_back(target);
         }
+    }
 
-        Ok(DispatchBuilder {
-            execution_matrix_cm,
-            systems: self.systems,
-            per_thread: Default::default(),
-            balanced_thread_count: 0,
-        })
+    // Walk back from the start to build the cycle
+    let mut cycle = Vec::new();
+    let mut node = start;
+    loop {
+        let edge = parents[&node];
+        let (source, _) = graph.edge_endpoints(edge).unwrap();
+        cycle.push((graph[source], graph[edge].clone()));
+        node = source;
+
+        if node == start {
+            break;
+        }
     }
+
+    cycle.reverse();
+    Some(cycle)
 }
@@ -1,21 +1,144 @@
-use crate::{Read, Resource, ResourceMask, WorldBorrowError, WorldBorrowMutError, Write};
+use crate::{
+    AccessRecord, Command, Commands, Events, Read, Resource, ResourceIds, ResourceMask,
+    WorldBorrowError, WorldBorrowMutError, Write,
+};
 use ahash::AHashMap;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-use std::{any::TypeId, cell::RefCell};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::{Ref, RefCell, RefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::ThreadId,
+};
 
 #[derive(Clone)]
 pub(crate) struct InternalData {
     pub read: ResourceMask,
     pub write: ResourceMask,
+    pub resources: Arc<ResourceIds>,
+
+    // Position of the current system within the schedule. Used to order the commands it queues
+    pub order: usize,
+
+    // Change tick of the current execution of the system, and of its previous execution
+    pub tick: u64,
+    pub last_run: u64,
+
+    // Only set when the dispatcher is auditing the accesses of the systems
+    pub accesses: Option<Vec<AccessRecord>>,
+}
+
+impl InternalData {
+    // Check if the current system is allowed to access the resource (and record the attempt if we are auditing)
+    // Resources that were never registered by the registry can't be part of any declared mask, so they are denied
+    // unless the mask covers everything (which is how run conditions get to read any resource)
+    fn check<R: Resource>(&mut self, mutable: bool) -> bool {
+        if let Some(accesses) = self.accesses.as_mut() {
+            accesses.push(AccessRecord {
+                name: type_name::<R>(),
+                mutable,
+            });
+        }
+
+        let mask = if mutable { self.write } else { self.read };
+        match self.resources.get::<R>() {
+            Some(bit) => mask.intersects(&bit),
+            None => mask == ResourceMask::FULL,
+        }
+    }
+}
+
+// A single resource slot. Slots are boxed and never removed from the world, so their address stays
+// stable even when new slots get added at runtime. Removed resources simply leave an empty slot behind
+#[derive(Default)]
+pub(crate) struct Slot {
+    value: RwLock<Option<Box<dyn Resource>>>,
+
+    // Tick of the last time the resource was inserted or mutably borrowed
+    changed: AtomicU64,
+}
+
+impl Slot {
+    fn new(resource: Box<dyn Resource>, tick: u64) -> Self {
+        Self {
+            value: RwLock::new(Some(resource)),
+            changed: AtomicU64::new(tick),
+        }
+    }
+
+    fn stamp(&self, tick: u64) {
+        self.changed.fetch_max(tick, Ordering::Relaxed);
+    }
+}
+
+// Resources that are neither Send nor Sync. They can only be accessed from the thread that inserted them,
+// which is why only main thread systems (executing on the thread that calls dispatch) can use them
+#[derive(Default)]
+pub(crate) struct NonSendStore {
+    owner: Option<ThreadId>,
+    values: AHashMap<TypeId, RefCell<Box<dyn Any>>>,
+}
+
+// SAFETY: The values are only ever accessed (or dropped) on the owner thread, which is checked on every access
+unsafe impl Send for NonSendStore {}
+unsafe impl Sync for NonSendStore {}
+
+impl NonSendStore {
+    fn on_owner(&self) -> bool {
+        self.owner == Some(std::thread::current().id())
+    }
+
+    fn cell<R: 'static>(&self) -> Result<&RefCell<Box<dyn Any>>, WorldBorrowError> {
+        if self.owner.is_some() && !self.on_owner() {
+            return Err(WorldBorrowError::InvalidAccess);
+        }
+
+        self.values
+            .get(&TypeId::of::<R>())
+            .ok_or(WorldBorrowError::NotPresent)
+    }
 }
 
+impl Drop for NonSendStore {
+    fn drop(&mut self) {
+        // We can't drop the values on another thread, so the best we can do is leak them
+        if !self.values.is_empty() && !self.on_owner() {
+            log::warn!(
+                "Leaking {} non-send resources since the world was dropped on another thread",
+                self.values.len()
+            );
+            std::mem::forget(std::mem::take(&mut self.values));
+        }
+    }
+}
+
+// Rotates the buffers of a type erased event channel
+type EventUpdater = fn(&mut dyn Resource);
+
 pub struct World {
-    pub(crate) resources: AHashMap<TypeId, RwLock<Box<dyn Resource>>>,
+    pub(crate) resources: RwLock<AHashMap<TypeId, Box<Slot>>>,
+    pub(crate) commands: Mutex<Vec<(usize, Command)>>,
+
+    // Event channels whose buffers get rotated at the end of every dispatch
+    pub(crate) events: Vec<(TypeId, EventUpdater)>,
+    pub(crate) non_send: NonSendStore,
+
+    // Global change tick. Advanced every time a system executes or a resource gets changed from outside of a system
+    pub(crate) tick: AtomicU64,
 }
 
 impl Default for World {
     fn default() -> Self {
-        let world = Self { resources: Default::default() };
+        let world = Self {
+            resources: Default::default(),
+            commands: Default::default(),
+            events: Default::default(),
+            non_send: Default::default(),
+            tick: AtomicU64::new(0),
+        };
         world.set_internal(None);
         world
     }
@@ -29,55 +152,236 @@ impl World {
     // Insert a resource to the world before we lock it up inside an Arc to be banished to the immutable realm
     pub fn insert<R: Resource>(&mut self, resource: R) {
         let id = TypeId::of::<R>();
-        self.resources.insert(id, RwLock::new(Box::new(resource)));
+        let slot = Slot::new(Box::new(resource), self.next_tick());
+        self.resources.get_mut().insert(id, Box::new(slot));
+    }
+
+    // Add an event channel (Events<T>) to the world, so the dispatcher can rotate its buffers after every dispatch
+    pub fn add_events<T: Send + Sync + 'static>(&mut self) {
+        let id = TypeId::of::<Events<T>>();
+        if !self.events.iter().any(|(x, _)| *x == id) {
+            self.events.push((id, Events::<T>::update_resource));
+        }
+
+        if !self.contains::<Events<T>>() {
+            self.insert(Events::<T>::default());
+        }
+    }
+
+    // Insert a resource that is not Send/Sync. The first insertion decides which thread owns the non-send resources
+    // Panics if called from another thread than the owner
+    pub fn insert_non_send<R: 'static>(&mut self, resource: R) {
+        let current = std::thread::current().id();
+        let owner = *self.non_send.owner.get_or_insert(current);
+        assert_eq!(
+            owner, current,
+            "Non-send resources must be inserted on the thread that owns them"
+        );
+        self.non_send
+            .values
+            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    // Remove a non-send resource from the world, returning it if it was present
+    // Panics if called from another thread than the owner
+    pub fn remove_non_send<R: 'static>(&mut self) -> Option<R> {
+        let id = TypeId::of::<R>();
+        if !self.non_send.values.contains_key(&id) {
+            return None;
+        }
+
+        assert!(
+            self.non_send.on_owner(),
+            "Non-send resources must be removed on the thread that owns them"
+        );
+        let cell = self.non_send.values.remove(&id).unwrap();
+        Some(*cell.into_inner().downcast::<R>().unwrap())
+    }
+
+    // Get an immutable reference to a non-send resource. Fails when not called from the owner thread
+    pub fn get_non_send<R: 'static>(&self) -> Result<Ref<'_, R>, WorldBorrowError> {
+        let cell = self.non_send.cell::<R>()?;
+        let borrowed = cell.try_borrow().map_err(WorldBorrowError::BorrowError)?;
+        Ok(Ref::map(borrowed, |x| x.downcast_ref::<R>().unwrap()))
+    }
+
+    // Get a mutable reference to a non-send resource. Fails when not called from the owner thread
+    pub fn get_non_send_mut<R: 'static>(&self) -> Result<RefMut<'_, R>, WorldBorrowMutError> {
+        let cell = self.non_send.cell::<R>().map_err(|err| match err {
+            WorldBorrowError::NotPresent => WorldBorrowMutError::NotPresent,
+            _ => WorldBorrowMutError::InvalidAccess,
+        })?;
+        let borrowed = cell
+            .try_borrow_mut()
+            .map_err(WorldBorrowMutError::BorrowMutError)?;
+        Ok(RefMut::map(borrowed, |x| x.downcast_mut::<R>().unwrap()))
+    }
+
+    // Remove a resource from the world, returning it if it was present
+    pub fn remove<R: Resource>(&mut self) -> Option<R> {
+        let slot = self.resources.get_mut().remove(&TypeId::of::<R>())?;
+        let boxed = slot.value.into_inner()?.into_any().downcast::<R>().unwrap();
+        Some(*boxed)
+    }
+
+    // Replace a resource in the world, returning the old value if there was one
+    pub fn replace<R: Resource>(&mut self, resource: R) -> Option<R> {
+        let old = self.remove::<R>();
+        self.insert(resource);
+        old
+    }
+
+    // Get a mutable reference to a resource, inserting it using the given function if it isn't present
+    pub fn get_or_insert_with<R: Resource>(&mut self, default: impl FnOnce() -> R) -> &mut R {
+        let tick = self.next_tick();
+        let slot = self
+            .resources
+            .get_mut()
+            .entry(TypeId::of::<R>())
+            .or_default();
+        slot.stamp(tick);
+        let boxed = slot
+            .value
+            .get_mut()
+            .get_or_insert_with(|| Box::new(default()));
+        (**boxed).as_any_mut().downcast_mut::<R>().unwrap()
+    }
+
+    // Get the slot of a resource type (if it was ever inserted)
+    fn slot(&self, id: TypeId) -> Option<&Slot> {
+        let slot: *const Slot = &**self.resources.read().get(&id)?;
+
+        // SAFETY: Slots are boxed and only get dropped (or replaced) through &mut self,
+        // so the slot outlives this borrow of the world even after we release the map lock
+        Some(unsafe { &*slot })
+    }
+
+    // Get a command queue that can insert or remove resources once the current dispatch finishes
+    pub fn commands(&self) -> Commands<'_> {
+        let order =
+            World::INTERNAL.with_borrow(|x| x.as_ref().map(|x| x.order).unwrap_or(usize::MAX));
+        Commands::new(self, order)
+    }
+
+    // Apply all the queued commands in schedule order. Commands of the same system are applied in the order they were queued,
+    // and commands queued from outside of the dispatcher get applied last. Called by the dispatcher once all of its threads are idle
+    pub(crate) fn apply_commands(&self) {
+        let mut commands = std::mem::take(&mut *self.commands.lock());
+        commands.sort_by_key(|(order, _)| *order);
+
+        for (_, command) in commands {
+            match command {
+                Command::Insert(id, resource) => {
+                    let slot = match self.slot(id) {
+                        Some(slot) => slot,
+                        None => {
+                            self.resources.write().insert(id, Default::default());
+                            self.slot(id).unwrap()
+                        }
+                    };
+                    *slot.value.write() = Some(resource);
+                    slot.stamp(self.next_tick());
+                }
+                Command::Remove(id) => {
+                    if let Some(slot) = self.slot(id) {
+                        *slot.value.write() = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // Rotate the buffers of all the event channels. Called by the dispatcher once all of its threads are idle
+    // This does not count as a change, since readers only care about the events themselves
+    pub(crate) fn update_events(&self) {
+        for (id, update) in self.events.iter() {
+            if let Some(slot) = self.slot(*id) {
+                if let Some(resource) = slot.value.write().as_deref_mut() {
+                    update(resource);
+                }
+            }
+        }
+    }
+
+    // Advance the global change tick, returning the new tick
+    pub(crate) fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    // Check if a resource was inserted or mutably borrowed since the current system last executed
+    // Outside of systems, this checks if the resource was ever changed. A system's own changes are not counted
+    pub fn changed_since_last_run<R: Resource>(&self) -> bool {
+        let last_run =
+            World::INTERNAL.with_borrow(|x| x.as_ref().map(|x| x.last_run).unwrap_or_default());
+        self.slot(TypeId::of::<R>())
+            .is_some_and(|x| x.changed.load(Ordering::Relaxed) > last_run)
     }
 
     pub(crate) fn set_internal(&self, data: Option<InternalData>) {
         World::INTERNAL.with_borrow_mut(|x| *x = data);
     }
 
+    // Take the accesses that the current system recorded while auditing
+    pub(crate) fn take_accesses(&self) -> Vec<AccessRecord> {
+        World::INTERNAL.with_borrow_mut(|x| {
+            x.as_mut()
+                .and_then(|x| x.accesses.as_mut())
+                .map(std::mem::take)
+                .unwrap_or_default()
+        })
+    }
+
     // Youssef was here writing a dumb comment about how this code is so unordered and not friendly to the eyes <3
     // Get an immutable reference (read guard) to a resource
-    pub fn get<R: Resource>(&self) -> Result<Read<R>, WorldBorrowError> {
-        let mask = World::INTERNAL
-            .with_borrow(|x| x.as_ref().map(|x| x.read).unwrap_or(ResourceMask::MAX));
+    pub fn get<R: Resource>(&self) -> Result<Read<'_, R>, WorldBorrowError> {
+        let allowed = World::INTERNAL
+            .with_borrow_mut(|x| x.as_mut().map(|x| x.check::<R>(false)).unwrap_or(true));
 
-        if (mask & R::mask()) == 0 {
+        if !allowed {
             return Err(WorldBorrowError::InvalidAccess);
         }
 
         let cell = self
-            .resources
-            .get(&TypeId::of::<R>())
+            .slot(TypeId::of::<R>())
             .ok_or(WorldBorrowError::NotPresent)?;
-        let mapped = RwLockReadGuard::map(cell.read(), |boxed| {
-            (**boxed).as_any_ref().downcast_ref::<R>().unwrap()
+        let mapped = RwLockReadGuard::try_map(cell.value.read(), |boxed| {
+            boxed
+                .as_deref()
+                .map(|x| x.as_any_ref().downcast_ref::<R>().unwrap())
         });
-        Ok(Read(mapped))
+        mapped.map(Read).map_err(|_| WorldBorrowError::NotPresent)
     }
 
     // Get a mutable reference (write guard) to a resource
-    pub fn get_mut<R: Resource>(&self) -> Result<Write<R>, WorldBorrowMutError> {
-        let mask = World::INTERNAL.with_borrow(|x: &Option<InternalData>| {
-            x.as_ref().map(|x| x.write).unwrap_or(ResourceMask::MAX)
+    pub fn get_mut<R: Resource>(&self) -> Result<Write<'_, R>, WorldBorrowMutError> {
+        let allowed = World::INTERNAL.with_borrow_mut(|x: &mut Option<InternalData>| {
+            x.as_mut().map(|x| x.check::<R>(true)).unwrap_or(true)
         });
-        if (mask & R::mask()) == 0 {
+        if !allowed {
             return Err(WorldBorrowMutError::InvalidAccess);
         }
 
         let cell = self
-            .resources
-            .get(&TypeId::of::<R>())
+            .slot(TypeId::of::<R>())
             .ok_or(WorldBorrowMutError::NotPresent)?;
-        let mapped = RwLockWriteGuard::map(cell.write(), |boxed| {
-            (**boxed).as_any_mut().downcast_mut::<R>().unwrap()
+        let mapped = RwLockWriteGuard::try_map(cell.value.write(), |boxed| {
+            boxed
+                .as_deref_mut()
+                .map(|x| x.as_any_mut().downcast_mut::<R>().unwrap())
         });
+        let mapped = mapped.map_err(|_| WorldBorrowMutError::NotPresent)?;
+
+        // Systems stamp with the tick of their current execution, anything else advances the global tick
+        let tick = World::INTERNAL.with_borrow(|x| x.as_ref().map(|x| x.tick));
+        cell.stamp(tick.unwrap_or_else(|| self.next_tick()));
         Ok(Write(mapped))
     }
 
     // Check if a resource is present in the world
+    // A slot that is currently locked must hold a resource, since guards only exist for present resources
     pub fn contains<R: Resource>(&self) -> bool {
-        self.resources.contains_key(&TypeId::of::<R>())
+        self.slot(TypeId::of::<R>())
+            .is_some_and(|x| x.value.try_read().map(|x| x.is_some()).unwrap_or(true))
     }
 
     // Get the current read and write resource masks for this thread (if any)
@@ -0,0 +1,74 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+fn system_a(world: &World) {
+    *world.get_mut::<i32>().unwrap() += 1;
+    assert!(world.get::<u32>().is_err());
+}
+
+fn system_b(world: &World) {
+    let _ = world.get::<i32>().unwrap();
+}
+
+fn system_c(world: &World) {
+    assert!(world.get_mut::<u64>().is_ok());
+}
+
+#[test]
+fn report() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(system_a)
+        .unwrap()
+        .writes::<i32>()
+        .reads::<u8>();
+    registry
+        .insert(system_b)
+        .unwrap()
+        .writes::<i32>()
+        .after(system_a);
+    registry.insert(system_c).unwrap().writes::<u64>();
+
+    let mut world = World::default();
+    world.insert(0i32);
+    world.insert(0u32);
+    world.insert(0u64);
+
+    let mut builder = registry.sort().unwrap();
+    builder.audit(true);
+    let mut dispatcher = builder.build(Arc::new(world), None);
+    dispatcher.dispatch();
+
+    let report = dispatcher.audit().unwrap();
+    assert!(!report.is_exact());
+
+    let a = report.get(StageId::of(&system_a)).unwrap();
+    assert_eq!(a.under_declared_reads(), vec!["u32"]);
+    assert_eq!(a.over_declared_reads(), vec!["u8"]);
+    assert!(a.over_declared_writes().is_empty());
+
+    let b = report.get(StageId::of(&system_b)).unwrap();
+    assert_eq!(b.over_declared_writes(), vec!["i32"]);
+    assert!(b.under_declared_reads().is_empty());
+
+    let c = report.get(StageId::of(&system_c)).unwrap();
+    assert!(c.is_exact());
+}
+
+#[test]
+fn disabled() {
+    let mut registry = Registry::default();
+    registry.insert(system_c).unwrap().writes::<u64>();
+
+    let mut world = World::default();
+    world.insert(0u64);
+    let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
+    dispatcher.dispatch();
+    assert!(dispatcher.audit().is_none());
+}
@@ -0,0 +1,70 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+static REBUILDS: AtomicUsize = AtomicUsize::new(0);
+
+fn rebuild(w: &World) {
+    if w.changed_since_last_run::<u32>() {
+        REBUILDS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn modify(w: &World) {
+    if *w.get::<bool>().unwrap() {
+        *w.get_mut::<u32>().unwrap() += 1;
+    }
+}
+
+#[test]
+fn ticks() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(rebuild)
+        .unwrap()
+        .before(modify)
+        .reads::<u32>();
+    registry
+        .insert(modify)
+        .unwrap()
+        .reads::<bool>()
+        .writes::<u32>();
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(false);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+    let mut dispatch = |expected: usize| {
+        dispatcher.dispatch().unwrap();
+        assert_eq!(REBUILDS.load(Ordering::Relaxed), expected);
+    };
+
+    // Inserting the resource counts as a change
+    dispatch(1);
+    dispatch(1);
+
+    // Modify executes after rebuild, so its changes are only seen on the next dispatch
+    *world.get_mut::<bool>().unwrap() = true;
+    dispatch(1);
+    dispatch(2);
+    dispatch(3);
+    *world.get_mut::<bool>().unwrap() = false;
+    dispatch(4);
+    dispatch(4);
+
+    // Changes from outside of the dispatcher are seen too
+    *world.get_mut::<u32>().unwrap() = 0;
+    dispatch(5);
+    dispatch(5);
+    assert!(world.changed_since_last_run::<u32>());
+    assert!(!world.changed_since_last_run::<i32>());
+}
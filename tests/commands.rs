@@ -0,0 +1,71 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+struct Counter(u32);
+
+fn spawn(mut commands: Commands) {
+    commands.insert(Counter(0));
+}
+
+fn despawn(mut commands: Commands, counter: Option<Read<Counter>>) {
+    if counter.is_some() {
+        commands.remove::<Counter>();
+    }
+}
+
+fn first(w: &World) {
+    w.commands().insert(1u32);
+}
+
+fn second(w: &World) {
+    w.commands().insert(2u32);
+}
+
+#[test]
+fn insert_remove() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert_system(despawn).unwrap();
+    registry.insert_system(spawn).unwrap().after(despawn);
+
+    let world = Arc::new(World::default());
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+    assert!(!world.contains::<Counter>());
+
+    // Despawn sees nothing, spawn queues the counter which gets inserted at the end of the dispatch
+    dispatcher.dispatch().unwrap();
+    assert_eq!(world.get::<Counter>().unwrap().0, 0);
+
+    // Spawn executes after despawn, so the insertion overwrites the removal
+    world.get_mut::<Counter>().unwrap().0 = 5;
+    dispatcher.dispatch().unwrap();
+    assert_eq!(world.get::<Counter>().unwrap().0, 0);
+
+    // Commands queued from the main thread are applied last
+    world.commands().remove::<Counter>();
+    dispatcher.dispatch().unwrap();
+    assert!(!world.contains::<Counter>());
+    assert!(world.get::<Counter>().is_err());
+}
+
+#[test]
+fn order() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(second).unwrap().after(first);
+    registry.insert(first).unwrap();
+
+    let world = Arc::new(World::default());
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
@@ -0,0 +1,82 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+#[derive(PartialEq)]
+enum GameState {
+    Menu,
+    Playing,
+}
+
+fn tick(w: &World) {
+    *w.get_mut::<u32>().unwrap() += 1;
+}
+
+fn write_state(w: &World) {
+    assert!(w.get_mut::<GameState>().is_err());
+}
+
+#[test]
+fn run_if() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(tick)
+        .unwrap()
+        .writes::<u32>()
+        .run_if(|w: &World| *w.get::<GameState>().unwrap() == GameState::Playing);
+
+    // Conditions only get read access to the world
+    registry
+        .insert(write_state)
+        .unwrap()
+        .run_if(|w: &World| w.get_mut::<GameState>().is_err());
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(GameState::Menu);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 0);
+
+    *world.get_mut::<GameState>().unwrap() = GameState::Playing;
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
+
+#[test]
+fn changed() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(tick)
+        .unwrap()
+        .writes::<u32>()
+        .run_if(|w: &World| w.changed_since_last_run::<GameState>());
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(GameState::Menu);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 1);
+
+    world.get_mut::<GameState>();
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
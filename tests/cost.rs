@@ -0,0 +1,137 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{sync::Arc, time::Duration};
+
+fn system_a(_: &World) {}
+fn system_b(_: &World) {}
+fn system_c(_: &World) {}
+fn system_d(_: &World) {}
+
+fn slow_a(_: &World) {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+fn slow_b(_: &World) {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[test]
+fn declared() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(system_a)
+        .unwrap()
+        .cost(Duration::from_millis(10));
+    registry
+        .insert(system_b)
+        .unwrap()
+        .cost(Duration::from_millis(1));
+    registry
+        .insert(system_c)
+        .unwrap()
+        .cost(Duration::from_millis(10));
+    registry.insert(system_d).unwrap();
+
+    // The two expensive systems get a thread each, and the cheap ones fill up the threads after them
+    let mut builder = registry.sort().unwrap();
+    assert_eq!(builder.makespan(), None);
+    assert_eq!(builder.stages_at(0, 0), None);
+    builder.balance(Some(2));
+    assert_eq!(builder.group(0).unwrap().len(), 4);
+    assert_eq!(
+        builder.stages_at(0, 0).unwrap(),
+        vec![StageId::of(&system_a), StageId::of(&system_d)]
+    );
+    assert_eq!(
+        builder.stages_at(0, 1).unwrap(),
+        vec![StageId::of(&system_c), StageId::of(&system_b)]
+    );
+
+    assert_eq!(builder.stage_at(0, 1), Some(StageId::of(&system_c)));
+    assert_eq!(builder.stage_at(0, 3), None);
+
+    // system_d has no cost, so it is assumed to be as expensive as the average (7ms)
+    assert_eq!(builder.makespan(), Some(Duration::from_millis(17)));
+}
+
+#[test]
+fn packed() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(system_a)
+        .unwrap()
+        .cost(Duration::from_millis(10));
+    registry
+        .insert(system_b)
+        .unwrap()
+        .cost(Duration::from_millis(1));
+    registry
+        .insert(system_c)
+        .unwrap()
+        .cost(Duration::from_millis(1));
+    registry
+        .insert(system_d)
+        .unwrap()
+        .cost(Duration::from_millis(1));
+
+    // The cheap systems all fit on the second thread while the first one executes the expensive system
+    let mut builder = registry.sort().unwrap();
+    builder.balance(Some(2));
+    assert_eq!(
+        builder.stages_at(0, 0).unwrap(),
+        vec![StageId::of(&system_a)]
+    );
+    assert_eq!(builder.stages_at(0, 1).unwrap().len(), 3);
+    assert_eq!(builder.makespan(), Some(Duration::from_millis(10)));
+
+    let mut dispatcher = builder.build(Arc::new(World::default()), None);
+    dispatcher.dispatch().unwrap();
+}
+
+#[test]
+fn learned() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    fn registry() -> Registry {
+        let mut registry = Registry::default();
+        registry.insert(slow_a).unwrap();
+        registry.insert(system_a).unwrap();
+        registry.insert(slow_b).unwrap();
+        registry.insert(system_b).unwrap();
+        registry
+    }
+
+    let mut builder = registry().sort().unwrap();
+    builder.profile(4);
+    let mut dispatcher = builder.build(Arc::new(World::default()), Some(1));
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+    let stats = dispatcher.stats().unwrap();
+
+    let mut builder = registry().sort().unwrap();
+    builder.learn(&stats);
+    builder.balance(Some(2));
+
+    // The slow systems end up on different threads
+    for thread in 0..2 {
+        let stages = builder.stages_at(0, thread).unwrap();
+        assert_ne!(
+            stages.contains(&StageId::of(&slow_a)),
+            stages.contains(&StageId::of(&slow_b))
+        );
+    }
+    assert!(builder.makespan().unwrap() >= Duration::from_millis(2));
+}
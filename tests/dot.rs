@@ -0,0 +1,51 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+
+fn system_a(_: &World) {}
+fn system_b(_: &World) {}
+fn system_c(_: &World) {}
+
+#[test]
+fn export() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(system_a)
+        .unwrap()
+        .writes::<u32>()
+        .reads::<i32>();
+    registry
+        .insert(system_b)
+        .unwrap()
+        .after(system_a)
+        .reads::<u32>();
+    registry.insert(system_c).unwrap().parallel(system_a);
+
+    let builder = registry.sort().unwrap();
+    let dot = builder.dot();
+
+    assert!(dot.starts_with("digraph schedule {"));
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains(&format!(
+        "[label=\"{}\\nR: i32\\nW: u32\"];",
+        StageId::of(&system_a).name
+    )));
+    assert!(dot.contains(&format!(
+        "[label=\"{}\\nR: u32\"];",
+        StageId::of(&system_b).name
+    )));
+    assert!(dot.contains(&format!(
+        "[label=\"{}\", shape=ellipse, style=dashed];",
+        StageId::of(&user).name
+    )));
+    assert!(dot.contains("label=\"group 0\";"));
+    assert!(dot.contains("label=\"group 1\";"));
+    assert!(!dot.contains("label=\"group 2\";"));
+    assert_eq!(dot.matches("[label=\"after\"]").count(), 3);
+    assert_eq!(dot.matches("[label=\"before\"]").count(), 2);
+    assert_eq!(dot.matches("style=dotted, dir=none").count(), 1);
+}
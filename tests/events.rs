@@ -0,0 +1,89 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+struct Frame(u32);
+
+fn writer(mut events: Write<Events<u32>>, mut frame: Write<Frame>) {
+    frame.0 += 1;
+    events.send(frame.0);
+    events.send(frame.0 * 10);
+}
+
+#[test]
+fn channel() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let early = Arc::new(Mutex::new(Vec::<u32>::new()));
+    let late = Arc::new(Mutex::new(Vec::<u32>::new()));
+
+    let mut registry = Registry::default();
+    registry.insert_system(writer).unwrap();
+
+    // Readers that execute before the writer see the events on the next dispatch
+    let (received, mut cursor) = (early.clone(), EventCursor::default());
+    registry
+        .insert(move |w: &World| {
+            let events = w.get::<Events<u32>>().unwrap();
+            received.lock().extend(cursor.read(&events).copied());
+        })
+        .unwrap()
+        .before(writer)
+        .reads::<Events<u32>>();
+
+    // Readers that execute after the writer see them on the same dispatch
+    let (received, mut cursor) = (late.clone(), EventCursor::default());
+    registry
+        .insert(move |w: &World| {
+            let events = w.get::<Events<u32>>().unwrap();
+            received.lock().extend(cursor.read(&events).copied());
+        })
+        .unwrap()
+        .after(writer)
+        .reads::<Events<u32>>();
+
+    let mut world = World::default();
+    world.insert(Frame(0));
+    world.add_events::<u32>();
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+
+    dispatcher.dispatch().unwrap();
+    assert!(early.lock().is_empty());
+    assert_eq!(*late.lock(), vec![1, 10]);
+
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*early.lock(), vec![1, 10, 2, 20]);
+    assert_eq!(*late.lock(), vec![1, 10, 2, 20, 3, 30]);
+
+    // Only the events of the last two dispatches are kept alive
+    let events = world.get::<Events<u32>>().unwrap();
+    assert_eq!(events.iter().copied().collect::<Vec<_>>(), vec![3, 30]);
+}
+
+#[test]
+fn buffers() {
+    let mut events = Events::<u32>::default();
+    let mut cursor = EventCursor::default();
+    events.send(0);
+    events.update();
+    events.send(1);
+    assert_eq!(events.len(), 2);
+    assert_eq!(cursor.read(&events).count(), 2);
+    assert_eq!(cursor.read(&events).count(), 0);
+
+    events.update();
+    events.update();
+    assert!(events.is_empty());
+
+    // A cursor that fell behind skips the events that were already dropped
+    let mut behind = EventCursor::default();
+    events.send(2);
+    assert_eq!(behind.read(&events).copied().collect::<Vec<_>>(), vec![2]);
+    assert_eq!(cursor.read(&events).copied().collect::<Vec<_>>(), vec![2]);
+}
@@ -0,0 +1,88 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+struct Level(u32);
+
+fn count(w: &World) {
+    *w.get_mut::<u32>().unwrap() += 1;
+}
+
+fn idle(_: &World) {}
+
+fn transition(w: &mut World) {
+    let level = w.remove::<Level>().unwrap();
+    w.insert(Level(level.0 + 1));
+    *w.get_or_insert_with(|| 0u64) += 1;
+}
+
+fn check(w: &World) {
+    assert_eq!(w.get::<Level>().unwrap().0 as u64, *w.get::<u64>().unwrap());
+}
+
+#[test]
+fn main() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(count).unwrap().writes::<u32>();
+    registry.insert(idle).unwrap();
+    registry.insert_exclusive(transition).unwrap().after(count);
+    registry
+        .insert(check)
+        .unwrap()
+        .after(transition)
+        .reads::<Level>()
+        .reads::<u64>();
+
+    // Exclusive systems never share their group, not even with systems that access nothing
+    let builder = registry.sort().unwrap();
+    assert_eq!(
+        builder.group(0),
+        Some(&vec![StageId::of(&count), StageId::of(&idle)])
+    );
+    assert_eq!(builder.group(1), Some(&vec![StageId::of(&transition)]));
+    assert_eq!(builder.group(2), Some(&vec![StageId::of(&check)]));
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(Level(0));
+    let mut dispatcher = builder.build(Arc::new(world), Some(2));
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+
+    let world = dispatcher.world();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+    assert_eq!(world.get::<Level>().unwrap().0, 2);
+}
+
+#[test]
+fn shared() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(count).unwrap().writes::<u32>();
+    registry.insert_exclusive(transition).unwrap();
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(Level(0));
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+
+    // We hold a clone of the world, so the exclusive system can't execute
+    let err = dispatcher.dispatch().unwrap_err();
+    assert_eq!(err.stages(), vec![StageId::of(&transition)]);
+    assert!(matches!(err.failures[0].cause, FailureCause::SharedWorld));
+    assert_eq!(*world.get::<u32>().unwrap(), 1);
+
+    drop(world);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(dispatcher.world().get::<Level>().unwrap().0, 1);
+}
@@ -0,0 +1,128 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+struct ResA;
+
+fn system_a(_: &World) {}
+fn system_b(_: &World) {}
+fn system_c(_: &World) {}
+
+// Keeps the systems in their topological order, one system per group
+struct Sequential(Arc<AtomicUsize>);
+
+impl GroupingStrategy for Sequential {
+    fn group(&self, nodes: &[GroupNode]) -> Vec<Vec<StageId>> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        nodes.iter().map(|x| vec![x.stage]).collect()
+    }
+}
+
+// Groups like the default strategy, but keeps the nodes around so we can look at them
+struct Recorder(Arc<Mutex<Vec<GroupNode>>>);
+
+impl GroupingStrategy for Recorder {
+    fn group(&self, nodes: &[GroupNode]) -> Vec<Vec<StageId>> {
+        *self.0.lock() = nodes.to_vec();
+        FirstFit.group(nodes)
+    }
+}
+
+// Puts every system into the same group
+struct Single;
+
+impl GroupingStrategy for Single {
+    fn group(&self, nodes: &[GroupNode]) -> Vec<Vec<StageId>> {
+        vec![nodes.iter().map(|x| x.stage).collect()]
+    }
+}
+
+#[test]
+fn custom() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut registry = Registry::default();
+    registry.set_grouping(Sequential(calls.clone()));
+    registry.insert(system_a).unwrap();
+    registry.insert(system_b).unwrap().after(system_a);
+    registry.insert(system_c).unwrap();
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0).unwrap().len(), 1);
+    assert_eq!(builder.group(1).unwrap().len(), 1);
+    assert_eq!(builder.group(2).unwrap().len(), 1);
+    assert!(builder.group(3).is_none());
+
+    // Rebuilding keeps using the strategy of the registry
+    let mut dispatcher = builder.build(Arc::new(World::default()), Some(2));
+    dispatcher.dispatch().unwrap();
+    dispatcher
+        .rebuild(|registry| {
+            registry.remove(StageId::of(&system_c));
+        })
+        .unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn invalid() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.set_grouping(Single);
+    registry.insert(system_a).unwrap().writes::<ResA>();
+    registry.insert(system_b).unwrap().writes::<ResA>();
+    let Err(RegistrySortingError::InvalidGrouping(_, reason)) = registry.sort() else {
+        panic!("expected an invalid grouping");
+    };
+    assert_eq!(reason, "it conflicts with another system of its group");
+
+    let mut registry = Registry::default();
+    registry.set_grouping(Single);
+    registry.insert(system_a).unwrap();
+    registry.insert(system_b).unwrap().after(system_a);
+    let Err(RegistrySortingError::InvalidGrouping(stage, _)) = registry.sort() else {
+        panic!("expected an invalid grouping");
+    };
+    assert_eq!(stage, StageId::of(&system_b));
+}
+
+#[test]
+fn nodes() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let nodes = Arc::new(Mutex::new(Vec::new()));
+    let mut registry = Registry::default();
+    registry.set_grouping(Recorder(nodes.clone()));
+    registry.insert(system_a).unwrap().main_thread();
+    registry
+        .insert(system_b)
+        .unwrap()
+        .cost(Duration::from_millis(3));
+    registry.sort().unwrap();
+
+    let nodes = nodes.lock();
+    let node = |stage: StageId| *nodes.iter().find(|x| x.stage == stage).unwrap();
+    let a = node(StageId::of(&system_a));
+    let b = node(StageId::of(&system_b));
+    assert!(a.main_thread && !b.main_thread);
+    assert_eq!(a.cost, None);
+    assert_eq!(b.cost, Some(Duration::from_millis(3)));
+}
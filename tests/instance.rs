@@ -0,0 +1,62 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+fn spawner(amount: u32) -> impl FnMut(&World) + Sync + Send + 'static {
+    move |w: &World| {
+        let mut spawned = w.get_mut::<Vec<u32>>().unwrap();
+        spawned.push(amount);
+    }
+}
+
+fn counter(mut count: Write<u64>) {
+    *count += 1;
+}
+
+#[test]
+fn named() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    let wave_1 = registry
+        .insert_named("spawn_wave_1", spawner(1))
+        .unwrap()
+        .writes::<Vec<u32>>()
+        .stage();
+    let wave_2 = registry
+        .insert_named("spawn_wave_2", spawner(2))
+        .unwrap()
+        .before(wave_1)
+        .writes::<Vec<u32>>()
+        .stage();
+    registry
+        .insert_named("spawn_wave_3", spawner(3))
+        .unwrap()
+        .after(wave_1)
+        .writes::<Vec<u32>>();
+    assert!(matches!(
+        registry.insert_named("spawn_wave_1", spawner(4)),
+        Err(StageError::Overlapping)
+    ));
+
+    // Instances of the same typed system
+    registry.insert_system_named("count_a", counter).unwrap();
+    registry.insert_system_named("count_b", counter).unwrap();
+
+    assert_eq!(wave_1, StageId::named(&spawner(0), "spawn_wave_1"));
+    assert_ne!(wave_1, wave_2);
+    assert_eq!(wave_1.id, wave_2.id);
+    assert_eq!(StageId::of(&wave_1), wave_1);
+
+    let mut world = World::default();
+    world.insert(Vec::<u32>::new());
+    world.insert(0u64);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<Vec<u32>>().unwrap(), vec![2, 1, 3]);
+    assert_eq!(*world.get::<u64>().unwrap(), 2);
+}
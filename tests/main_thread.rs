@@ -0,0 +1,116 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{cell::Cell, rc::Rc, sync::Arc};
+
+// Rc is neither Send nor Sync, so it can only be stored as a non-send resource
+struct Window(Rc<Cell<u32>>);
+
+fn present(w: &World) {
+    let window = w.get_non_send::<Window>().unwrap();
+    window.0.set(window.0.get() + 1);
+    *w.get_mut::<u32>().unwrap() = window.0.get();
+}
+
+fn simulate(w: &World) {
+    assert!(matches!(
+        w.get_non_send::<Window>(),
+        Err(WorldBorrowError::InvalidAccess)
+    ));
+    *w.get_mut::<u32>().unwrap() *= 10;
+}
+
+fn scale(w: &World) {
+    *w.get_mut::<u32>().unwrap() *= 10;
+}
+
+fn other(w: &World) {
+    *w.get_mut::<u64>().unwrap() += 1;
+}
+
+#[test]
+fn non_send() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(present)
+        .unwrap()
+        .main_thread()
+        .writes::<u32>();
+    registry
+        .insert(simulate)
+        .unwrap()
+        .after(present)
+        .writes::<u32>();
+    registry
+        .insert(other)
+        .unwrap()
+        .main_thread()
+        .writes::<u64>();
+
+    let counter = Rc::new(Cell::new(0));
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(0u64);
+    world.insert_non_send(Window(counter.clone()));
+    let world = Arc::new(world);
+
+    let mut builder = registry.sort().unwrap();
+    builder.profile(4);
+    let mut dispatcher = builder.build(world.clone(), Some(1));
+    dispatcher.dispatch().unwrap();
+    dispatcher.dispatch().unwrap();
+
+    assert_eq!(counter.get(), 2);
+    assert_eq!(*world.get::<u32>().unwrap(), 20);
+    assert_eq!(*world.get::<u64>().unwrap(), 2);
+    assert!(dispatcher
+        .stats()
+        .unwrap()
+        .system(StageId::of(&present))
+        .is_some());
+
+    // Main thread systems can be toggled like any other system
+    dispatcher.set_enabled(StageId::of(&present), false);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(counter.get(), 2);
+
+    drop(dispatcher);
+    let mut world = Arc::into_inner(world).unwrap();
+    assert_eq!(world.remove_non_send::<Window>().unwrap().0.get(), 2);
+}
+
+#[test]
+fn only_main() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(other)
+        .unwrap()
+        .main_thread()
+        .writes::<u64>();
+
+    let mut world = World::default();
+    world.insert(0u64);
+    world.insert(1u32);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u64>().unwrap(), 1);
+
+    // Rebuilding can still add worker systems
+    dispatcher
+        .rebuild(|registry| {
+            registry.insert(scale).unwrap().writes::<u32>();
+        })
+        .unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 10);
+}
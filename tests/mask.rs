@@ -0,0 +1,145 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+
+struct Wide<const N: usize>;
+struct Grid<const A: usize, const B: usize>;
+
+macro_rules! writes_all {
+    ($order:expr; $($n:literal)*) => {
+        $order$(.writes::<Wide<$n>>())*
+    };
+}
+
+// Writes to 23 distinct resources, so 23 rows go past the limit of 512 resources
+fn row<const A: usize>(order: InjectionOrder<'_>) -> InjectionOrder<'_> {
+    macro_rules! writes_row {
+        ($($n:literal)*) => {
+            order$(.writes::<Grid<A, $n>>())*
+        };
+    }
+
+    writes_row!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22)
+}
+
+fn system_a(_: &World) {}
+fn system_b(_: &World) {}
+fn system_c(_: &World) {}
+
+#[test]
+fn bits() {
+    let mut mask = ResourceMask::default();
+    assert!(mask.is_empty());
+    assert!(mask.set(3));
+    assert!(mask.set(200));
+    assert!(!mask.set(ResourceMask::BITS));
+    assert!(mask.contains_bit(200));
+    assert!(!mask.contains_bit(64));
+    assert_eq!(mask.bits().collect::<Vec<_>>(), vec![3, 200]);
+    assert!(mask.intersects(&ResourceMask::from_bit(200).unwrap()));
+    assert!(!mask.intersects(&ResourceMask::from_bit(199).unwrap()));
+    assert!(ResourceMask::from_bit(ResourceMask::BITS).is_none());
+}
+
+#[test]
+fn wide() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+
+    let order = registry.insert(system_a).unwrap();
+    writes_all!(order;
+        0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19
+        20 21 22 23 24 25 26 27 28 29 30 31 32 33 34 35 36 37 38 39
+        40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59
+        60 61 62 63 64 65 66 67 68 69
+    );
+    registry.insert(system_b).unwrap().reads::<Wide<69>>();
+    registry.insert(system_c).unwrap().reads::<Wide<70>>();
+
+    let writes = registry.mask::<Wide<69>>().unwrap() | registry.mask::<Wide<0>>().unwrap();
+    assert_eq!(writes.count(), 2);
+    assert_eq!(registry.resources().len(), 71);
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(
+        builder.group(0),
+        Some(&vec![StageId::of(&system_a), StageId::of(&system_c)])
+    );
+    assert_eq!(builder.group(1), Some(&vec![StageId::of(&system_b)]));
+}
+
+#[test]
+fn independent() {
+    let mut first = Registry::default();
+    first
+        .insert(system_a)
+        .unwrap()
+        .writes::<Wide<0>>()
+        .reads::<Wide<1>>();
+
+    let mut second = Registry::default();
+    second.insert(system_a).unwrap().reads::<Wide<1>>();
+
+    // Each registry allocates its own bits, starting from zero
+    assert_eq!(
+        first.mask::<Wide<1>>().unwrap(),
+        ResourceMask::from_bit(1).unwrap()
+    );
+    assert_eq!(
+        second.mask::<Wide<1>>().unwrap(),
+        ResourceMask::from_bit(0).unwrap()
+    );
+    assert_eq!(
+        second.resources().name(0),
+        Some(std::any::type_name::<Wide<1>>())
+    );
+    assert!(second.resources().get::<Wide<0>>().is_none());
+}
+
+#[test]
+fn limit() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    let order = registry.insert(system_a).unwrap();
+    let order = row::<0>(row::<1>(row::<2>(row::<3>(row::<4>(row::<5>(order))))));
+    let order = row::<6>(row::<7>(row::<8>(row::<9>(row::<10>(row::<11>(order))))));
+    let order = row::<12>(row::<13>(row::<14>(row::<15>(row::<16>(row::<17>(order))))));
+    row::<18>(row::<19>(row::<20>(row::<21>(row::<22>(order)))));
+
+    // Resources past the limit don't get a bit, and asking for one returns an error instead of panicking
+    assert_eq!(registry.resources().len(), ResourceMask::BITS);
+    assert!(matches!(
+        registry.mask::<Wide<0>>(),
+        Err(ResourceMaskError::LimitReached(_, ResourceMask::BITS))
+    ));
+
+    let Err(RegistrySortingError::InvalidResourceMask(stage, error)) = registry.sort() else {
+        panic!("expected an invalid resource mask");
+    };
+    assert_eq!(stage, StageId::of(&system_a));
+    assert!(matches!(
+        error,
+        ResourceMaskError::LimitReached(_, ResourceMask::BITS)
+    ));
+
+    // The world itself does not care about the limit
+    let mut world = World::default();
+    world.insert(Grid::<22, 22>);
+    assert!(world.get::<Grid<22, 22>>().is_ok());
+    assert!(world.get_mut::<Grid<22, 22>>().is_ok());
+    assert!(matches!(
+        world.get::<Grid<22, 21>>(),
+        Err(WorldBorrowError::NotPresent)
+    ));
+    assert!(matches!(
+        world.get_mut::<Grid<22, 21>>(),
+        Err(WorldBorrowMutError::NotPresent)
+    ));
+}
@@ -63,5 +63,45 @@ fn err() {
 
     registry.insert(system_a).unwrap().after(system_b);
     registry.insert(system_b).unwrap().after(system_a);
-    assert!(registry.sort().is_err());
-}
\ No newline at end of file
+    let Err(RegistrySortingError::CyclicRules(cycle)) = registry.sort() else {
+        panic!("expected a cycle");
+    };
+
+    assert_eq!(cycle.len(), 2);
+    assert!(cycle
+        .iter()
+        .any(|(stage, _)| *stage == StageId::of(&system_a)));
+    assert!(cycle
+        .iter()
+        .any(|(stage, _)| *stage == StageId::of(&system_b)));
+    assert!(cycle
+        .iter()
+        .all(|(_, origin)| matches!(origin, RuleOrigin::Rule(_, InjectionRule::After(_)))));
+}
+
+#[test]
+fn longest_path() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    // system_e depends on the end of a long chain and on a system without any rules
+    // Its depth must come from the deepest one of them, no matter which one gets visited last
+    let mut registry = Registry::default();
+    registry.insert(system_b).unwrap().after(system_a);
+    registry.insert(system_c).unwrap().after(system_b);
+    registry.insert(system_d).unwrap();
+    registry
+        .insert(system_e)
+        .unwrap()
+        .after(system_c)
+        .after(system_d);
+    registry.insert(system_a).unwrap();
+
+    let builder = registry.sort().unwrap();
+    let group = |stage: StageId| (0..).find(|x| builder.group(*x).unwrap().contains(&stage));
+    let e = group(StageId::of(&system_e)).unwrap();
+    assert!(group(StageId::of(&system_c)).unwrap() < e);
+    assert!(group(StageId::of(&system_d)).unwrap() < e);
+}
@@ -0,0 +1,84 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{panic::AssertUnwindSafe, sync::Arc};
+
+fn system_a(_: &World) {
+    panic!("boom");
+}
+
+fn system_b(world: &World) {
+    *world.get_mut::<u32>().unwrap() += 1;
+}
+
+fn build(policy: PanicPolicy) -> (Dispatcher, Arc<World>) {
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap();
+    registry
+        .insert(system_b)
+        .unwrap()
+        .after(system_a)
+        .writes::<u32>();
+
+    let mut world = World::default();
+    world.insert(0u32);
+    let world = Arc::new(world);
+
+    let mut builder = registry.sort().unwrap();
+    builder.panic_policy(policy);
+    (builder.build(world.clone(), None), world)
+}
+
+#[test]
+fn abort() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let (mut dispatcher, world) = build(PanicPolicy::AbortFrame);
+
+    for _ in 0..2 {
+        let err = dispatcher.dispatch().unwrap_err();
+        assert_eq!(err.stages(), vec![StageId::of(&system_a)]);
+        assert_eq!(err.failures[0].cause.panic_message(), Some("boom"));
+    }
+
+    // The panic aborted the frame before system_b could execute
+    assert_eq!(*world.get::<u32>().unwrap(), 0);
+}
+
+#[test]
+fn disable() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let (mut dispatcher, world) = build(PanicPolicy::Disable);
+
+    let err = dispatcher.dispatch().unwrap_err();
+    assert!(matches!(err.failures[0].cause, FailureCause::Panic(_)));
+    assert!(dispatcher.dispatch().is_ok());
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
+
+#[test]
+fn resume() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let (mut dispatcher, world) = build(PanicPolicy::Resume);
+
+    let payload = std::panic::catch_unwind(AssertUnwindSafe(|| dispatcher.dispatch())).unwrap_err();
+    let panic = payload.downcast_ref::<SystemPanic>().unwrap();
+    assert_eq!(panic.stage, StageId::of(&system_a));
+    assert_eq!(panic.message(), Some("boom"));
+    assert_eq!(panic.payload.downcast_ref::<&str>(), Some(&"boom"));
+    assert_eq!(*world.get::<u32>().unwrap(), 1);
+
+    // The threads survived the panic so we can keep dispatching
+    std::panic::catch_unwind(AssertUnwindSafe(|| dispatcher.dispatch())).unwrap_err();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
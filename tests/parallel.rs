@@ -126,9 +126,21 @@ fn err() {
 
     let mut registry = Registry::default();
 
-    registry.insert(system_a).unwrap().writes::<ResA>().parallel(system_b);
-    registry.insert(system_b).unwrap().writes::<ResA>().parallel(system_a);
-    assert!(registry.sort().is_err());
+    registry
+        .insert(system_a)
+        .unwrap()
+        .writes::<ResA>()
+        .parallel(system_b);
+    registry
+        .insert(system_b)
+        .unwrap()
+        .writes::<ResA>()
+        .parallel(system_a);
+    let Err(RegistrySortingError::UnsatisfiableParallelRules(_, _, conflicts)) = registry.sort()
+    else {
+        panic!("expected unsatisfiable parallel rules");
+    };
+    assert_eq!(conflicts, vec![std::any::type_name::<ResA>()]);
 }
 
 #[test]
@@ -148,7 +160,10 @@ fn balancing() {
 
     let mut builder = registry.sort().unwrap();
     builder.balance(Some(2));
-    assert_eq!(builder.group(0).unwrap().len(), 2);
-    assert_eq!(builder.group(1).unwrap().len(), 2);
-    assert_eq!(builder.group(2).unwrap().len(), 1);
+    assert_eq!(builder.group(0).unwrap().len(), 5);
+    assert!(builder.group(1).is_none());
+
+    // Systems without any cost are spread evenly over the threads
+    assert_eq!(builder.stages_at(0, 0).unwrap().len(), 3);
+    assert_eq!(builder.stages_at(0, 1).unwrap().len(), 2);
 }
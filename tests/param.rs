@@ -0,0 +1,92 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+struct Position(f32);
+struct Velocity(f32);
+
+fn physics(mut pos: Write<Position>, vel: Read<Velocity>) {
+    pos.0 += vel.0;
+}
+
+fn accelerate(mut vel: Write<Velocity>) {
+    vel.0 += 1.0;
+}
+
+fn report(pos: Read<Position>, missing: Option<Read<u32>>) {
+    assert_eq!(pos.0, 3.0);
+    assert!(missing.is_none());
+}
+
+fn aliased(_: Read<Position>, _: Write<Position>) {}
+
+fn shared(_: Read<Velocity>, _: Option<Read<Velocity>>) {}
+
+#[test]
+fn masks() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert_system(physics).unwrap();
+    registry.insert_system(accelerate).unwrap();
+
+    // Both systems touch velocity, and accelerate writes it, so they cannot share a group
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0).unwrap().len(), 1);
+    assert_eq!(builder.group(1).unwrap().len(), 1);
+}
+
+#[test]
+fn run() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert_system(accelerate).unwrap();
+    registry.insert_system(physics).unwrap().after(accelerate);
+    registry.insert_system(report).unwrap().after(physics);
+
+    let mut world = World::default();
+    world.insert(Position(1.0));
+    world.insert(Velocity(1.0));
+    let world = Arc::new(world);
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0), Some(&vec![StageId::of(&accelerate)]));
+    assert_eq!(builder.group(1), Some(&vec![StageId::of(&physics)]));
+    assert_eq!(builder.group(2), Some(&vec![StageId::of(&report)]));
+
+    let mut dispatcher = builder.build(world.clone(), None);
+    dispatcher.dispatch();
+    assert_eq!(world.get::<Velocity>().unwrap().0, 2.0);
+}
+
+#[test]
+fn conflicts() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    // Fetching both guards would deadlock, so sorting fails instead
+    let mut registry = Registry::default();
+    registry.insert_system(aliased).unwrap();
+    let Err(RegistrySortingError::InvalidResourceMask(stage, error)) = registry.sort() else {
+        panic!("expected conflicting parameters");
+    };
+    assert_eq!(stage, StageId::of(&aliased));
+    assert!(matches!(
+        error,
+        ResourceMaskError::ConflictingAccess(name) if name == std::any::type_name::<Position>()
+    ));
+
+    // Reading the same resource twice is fine
+    let mut registry = Registry::default();
+    registry.insert_system(shared).unwrap();
+    assert!(registry.sort().is_ok());
+}
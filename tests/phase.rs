@@ -0,0 +1,115 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+
+struct PreUpdate;
+struct Update;
+struct Render;
+
+fn input(_: &World) {}
+fn physics(_: &World) {}
+fn animation(_: &World) {}
+fn draw(_: &World) {}
+fn misc(_: &World) {}
+
+#[test]
+fn phases() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    assert!(registry.add_phase(PreUpdate));
+    assert!(registry.add_phase(Update));
+    assert!(registry.add_phase(Render));
+    assert!(!registry.add_phase(Update));
+
+    registry.insert(draw).unwrap().in_phase(Render);
+    registry
+        .insert(animation)
+        .unwrap()
+        .in_phase(Update)
+        .after(physics);
+    registry.insert(physics).unwrap().in_phase(Update);
+    registry.insert(input).unwrap().in_phase(PreUpdate);
+    registry.insert(misc).unwrap();
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0), Some(&vec![StageId::of(&misc)]));
+    assert_eq!(builder.group(1), Some(&vec![StageId::of(&input)]));
+    assert_eq!(builder.group(2), Some(&vec![StageId::of(&physics)]));
+    assert_eq!(builder.group(3), Some(&vec![StageId::of(&animation)]));
+    assert_eq!(builder.group(4), Some(&vec![StageId::of(&draw)]));
+    assert_eq!(builder.group(5), None);
+}
+
+#[test]
+fn sets() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    // Systems in a phase are also part of the set with the same label
+    let mut registry = Registry::default();
+    registry.add_phase(Update);
+    registry.insert(physics).unwrap().in_phase(Update);
+    registry
+        .insert(animation)
+        .unwrap()
+        .in_phase(Update)
+        .after_set(Update);
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0), Some(&vec![StageId::of(&physics)]));
+    assert_eq!(builder.group(1), Some(&vec![StageId::of(&animation)]));
+
+    // Phases must be added to the registry before systems can use them
+    let mut registry = Registry::default();
+    registry.insert(physics).unwrap().in_phase(Update);
+    assert!(matches!(
+        registry.sort(),
+        Err(RegistrySortingError::MissingStage(_, _))
+    ));
+}
+
+fn chain_a(_: &World) {}
+fn chain_b(_: &World) {}
+fn chain_c(_: &World) {}
+
+#[test]
+fn uneven() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    // The end of a phase must wait for the longest chain of systems within the phase
+    let mut registry = Registry::default();
+    registry.add_phase(Update);
+    registry.add_phase(Render);
+    registry.insert(chain_a).unwrap().in_phase(Update);
+    registry
+        .insert(chain_b)
+        .unwrap()
+        .in_phase(Update)
+        .after(chain_a);
+    registry
+        .insert(chain_c)
+        .unwrap()
+        .in_phase(Update)
+        .after(chain_b);
+    registry.insert(misc).unwrap().in_phase(Update);
+    registry.insert(draw).unwrap().in_phase(Render);
+
+    let builder = registry.sort().unwrap();
+    let group = |stage: StageId| {
+        (0..)
+            .find(|i| builder.group(*i).unwrap().contains(&stage))
+            .unwrap()
+    };
+    assert_eq!(group(StageId::of(&chain_a)), 0);
+    assert_eq!(group(StageId::of(&misc)), 0);
+    assert_eq!(group(StageId::of(&chain_c)), 2);
+    assert_eq!(group(StageId::of(&draw)), 3);
+}
@@ -0,0 +1,150 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{collections::HashSet, sync::Arc, thread::ThreadId};
+
+use parking_lot::Mutex;
+
+fn system_a(w: &World) {
+    *w.get_mut::<u32>().unwrap() += 1;
+}
+
+fn system_b(w: &World) {
+    *w.get_mut::<u32>().unwrap() *= 10;
+}
+
+fn system_c(w: &World) {
+    *w.get_mut::<u64>().unwrap() += 1;
+}
+
+fn system_d(w: &World) {
+    *w.get_mut::<u64>().unwrap() = 0;
+}
+
+// Records the thread that it executed on
+fn record(threads: Arc<Mutex<HashSet<ThreadId>>>) -> impl FnMut(&World) + Send + Sync {
+    move |_| {
+        threads.lock().insert(std::thread::current().id());
+    }
+}
+
+fn world() -> Arc<World> {
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(0u64);
+    Arc::new(world)
+}
+
+#[test]
+fn toggle() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap().writes::<u32>();
+    registry.insert(system_c).unwrap().writes::<u64>();
+
+    let world = world();
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), Some(2));
+    dispatcher.dispatch().unwrap();
+    assert!(dispatcher.set_enabled(StageId::of(&system_a), false));
+    assert!(!dispatcher.set_enabled(StageId::of(&system_b), false));
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 1);
+    assert_eq!(*world.get::<u64>().unwrap(), 2);
+
+    dispatcher.set_enabled(StageId::of(&system_a), true);
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+
+    assert!(dispatcher.remove(StageId::of(&system_c)));
+    assert!(!dispatcher.remove(StageId::of(&system_c)));
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u64>().unwrap(), 3);
+}
+
+#[test]
+fn rebuild() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap().writes::<u32>();
+
+    let world = world();
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), Some(2));
+    dispatcher.dispatch().unwrap();
+
+    dispatcher
+        .rebuild(|registry| {
+            assert!(registry.contains(StageId::of(&system_a)));
+            registry
+                .insert(system_b)
+                .unwrap()
+                .after(system_a)
+                .writes::<u32>();
+            registry.insert(system_c).unwrap().writes::<u64>();
+        })
+        .unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 20);
+    assert_eq!(*world.get::<u64>().unwrap(), 1);
+
+    // Cyclic rules fail to sort, so the previous schedule is kept and the new system is dropped
+    let err = dispatcher.rebuild(|registry| {
+        registry
+            .insert(system_d)
+            .unwrap()
+            .after(system_b)
+            .before(system_a)
+            .writes::<u64>();
+    });
+    assert!(matches!(err, Err(RegistrySortingError::CyclicRules(_))));
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 210);
+    assert_eq!(*world.get::<u64>().unwrap(), 2);
+
+    // Removing a system drops the rules that other systems had on it
+    dispatcher
+        .rebuild(|registry| {
+            assert!(registry.remove(StageId::of(&system_a)));
+        })
+        .unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*world.get::<u32>().unwrap(), 2100);
+}
+
+#[test]
+fn grow() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let mut registry = Registry::default();
+    registry
+        .insert_named("record_0", record(threads.clone()))
+        .unwrap();
+
+    // The initial schedule only fills a single thread, but all the requested threads stay around
+    let mut dispatcher = registry.sort().unwrap().build(world(), Some(4));
+    dispatcher
+        .rebuild(|registry| {
+            registry
+                .insert_named("record_1", record(threads.clone()))
+                .unwrap();
+            registry
+                .insert_named("record_2", record(threads.clone()))
+                .unwrap();
+            registry
+                .insert_named("record_3", record(threads.clone()))
+                .unwrap();
+        })
+        .unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(threads.lock().len(), 4);
+}
@@ -58,3 +58,52 @@ fn err() {
     let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
     dispatcher.dispatch();
 }
+
+#[test]
+fn lifecycle() {
+    let mut world = World::default();
+    assert_eq!(world.remove::<i32>(), None);
+    assert_eq!(world.replace(1i32), None);
+    assert_eq!(world.replace(2i32), Some(1));
+
+    *world.get_or_insert_with(|| 10i32) += 1;
+    *world.get_or_insert_with(|| 10u32) += 1;
+    assert_eq!(*world.get::<i32>().unwrap(), 3);
+    assert_eq!(*world.get::<u32>().unwrap(), 11);
+
+    assert_eq!(world.remove::<i32>(), Some(3));
+    assert!(!world.contains::<i32>());
+    assert!(matches!(
+        world.get::<i32>(),
+        Err(WorldBorrowError::NotPresent)
+    ));
+}
+
+#[test]
+fn exclusive() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap().writes::<i32>();
+
+    let mut world = World::default();
+    world.insert(0i32);
+    let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
+
+    for _ in 0..2 {
+        dispatcher.dispatch().unwrap();
+        let old = dispatcher.with_world(|world| world.replace(10i32)).unwrap();
+        assert!(matches!(old, Some(1) | Some(11)));
+    }
+
+    assert_eq!(dispatcher.world_mut().unwrap().remove::<i32>(), Some(10));
+
+    // Keeping a clone of the world around prevents exclusive access
+    let world = dispatcher.world().clone();
+    assert!(dispatcher.world_mut().is_none());
+    drop(world);
+    assert!(dispatcher.world_mut().is_some());
+}
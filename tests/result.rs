@@ -0,0 +1,76 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+struct Missing;
+
+fn system_a(world: &World) -> Result<(), String> {
+    let mut value = world.get_mut::<i32>().unwrap();
+    *value += 1;
+
+    if *value == 1 {
+        Err("first frame".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn system_b(world: &World) -> Result<(), WorldBorrowError> {
+    world.get::<Missing>()?;
+    Ok(())
+}
+
+fn system_c(_: Read<Missing>) {}
+
+fn system_d(world: &World) {
+    *world.get_mut::<u32>().unwrap() += 1;
+}
+
+#[test]
+fn errors() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap().writes::<i32>();
+    registry.insert(system_b).unwrap().reads::<Missing>();
+    registry.insert_system(system_c).unwrap();
+    registry.insert(system_d).unwrap().writes::<u32>();
+
+    let mut world = World::default();
+    world.insert(0i32);
+    world.insert(0u32);
+    let world = Arc::new(world);
+    let mut dispatcher = registry.sort().unwrap().build(world.clone(), None);
+
+    let err = dispatcher.dispatch().unwrap_err();
+    let mut stages = err.stages();
+    stages.sort();
+    assert_eq!(
+        stages,
+        vec![
+            StageId::of(&system_a),
+            StageId::of(&system_b),
+            StageId::of(&system_c)
+        ]
+    );
+
+    // Failing systems do not stop the other systems, and the dispatcher is still usable
+    let err = dispatcher.dispatch().unwrap_err();
+    assert_eq!(err.failures.len(), 2);
+    assert_eq!(*world.get::<i32>().unwrap(), 2);
+    assert_eq!(*world.get::<u32>().unwrap(), 2);
+}
+
+#[test]
+fn ok() {
+    let mut registry = Registry::default();
+    registry.insert(system_d).unwrap().writes::<u32>();
+
+    let mut world = World::default();
+    world.insert(0u32);
+    let mut dispatcher = registry.sort().unwrap().build(Arc::new(world), None);
+    assert!(dispatcher.dispatch().is_ok());
+}
@@ -0,0 +1,72 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+
+struct Physics;
+struct Audio;
+
+fn input(_: &World) {}
+fn gravity(_: &World) {}
+fn collisions(_: &World) {}
+fn render(_: &World) {}
+
+#[test]
+fn order() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(render)
+        .unwrap()
+        .after_set(Physics)
+        .after_set(Audio);
+    registry
+        .insert(gravity)
+        .unwrap()
+        .in_set(Physics)
+        .writes::<u32>();
+    registry
+        .insert(collisions)
+        .unwrap()
+        .in_set(Physics)
+        .writes::<u64>();
+    registry.insert(input).unwrap().before_set(Physics);
+
+    let builder = registry.sort().unwrap();
+    assert_eq!(builder.group(0), Some(&vec![StageId::of(&input)]));
+    assert_eq!(builder.group(1).unwrap().len(), 2);
+    assert!(builder.group(1).unwrap().contains(&StageId::of(&gravity)));
+    assert!(builder
+        .group(1)
+        .unwrap()
+        .contains(&StageId::of(&collisions)));
+    assert_eq!(builder.group(2), Some(&vec![StageId::of(&render)]));
+}
+
+#[test]
+fn err() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(gravity).unwrap().in_set(Physics);
+    registry
+        .insert(collisions)
+        .unwrap()
+        .in_set(Physics)
+        .before_set(Audio);
+    registry
+        .insert(render)
+        .unwrap()
+        .in_set(Audio)
+        .before_set(Physics);
+
+    let Err(RegistrySortingError::CyclicRules(cycle)) = registry.sort() else {
+        panic!("expected a cycle");
+    };
+    assert_eq!(cycle.len(), 2);
+}
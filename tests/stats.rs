@@ -0,0 +1,62 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{sync::Arc, time::Duration};
+
+fn system_a(_: &World) {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+fn system_b(_: &World) {}
+
+#[test]
+fn profile() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(system_a)
+        .unwrap()
+        .cost(Duration::from_millis(5));
+    registry.insert(system_b).unwrap().cost(Duration::ZERO);
+
+    // The declared costs keep the two systems on different threads
+    let mut builder = registry.sort().unwrap();
+    builder.balance(Some(2));
+    builder.profile(4);
+    let fast = (0..2)
+        .position(|thread| builder.stages_at(0, thread) == Some(vec![StageId::of(&system_b)]))
+        .unwrap();
+
+    let mut dispatcher = builder.build(Arc::new(World::default()), None);
+    for _ in 0..6 {
+        dispatcher.dispatch().unwrap();
+    }
+
+    let stats = dispatcher.stats().unwrap();
+    assert_eq!(stats.frame.samples, 4);
+    assert!(stats.frame.min >= Duration::from_millis(5));
+
+    let (slowest, timing) = stats.slowest().unwrap();
+    assert_eq!(slowest, StageId::of(&system_a));
+    assert!(timing.min >= Duration::from_millis(5));
+    assert!(timing.avg <= timing.max);
+    assert_eq!(stats.system(StageId::of(&system_b)).unwrap().samples, 4);
+
+    // The thread executing the fast system has to wait for the slow one
+    assert!(stats.idle(fast, 0).unwrap().min >= Duration::from_millis(1));
+}
+
+#[test]
+fn disabled() {
+    let mut registry = Registry::default();
+    registry.insert(system_b).unwrap();
+    let mut dispatcher = registry
+        .sort()
+        .unwrap()
+        .build(Arc::new(World::default()), None);
+    dispatcher.dispatch().unwrap();
+    assert!(dispatcher.stats().is_none());
+}
@@ -0,0 +1,205 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::{sync::Arc, time::Duration};
+
+struct Log(Vec<&'static str>);
+
+fn add(w: &World) {
+    *w.get_mut::<u32>().unwrap() += 1;
+}
+
+fn multiply(w: &World) {
+    *w.get_mut::<u32>().unwrap() *= 10;
+}
+
+fn count(w: &World) {
+    *w.get_mut::<u64>().unwrap() += 1;
+}
+
+fn present(w: &World) {
+    let value = *w.get::<u32>().unwrap();
+    w.get_mut::<Log>()
+        .unwrap()
+        .0
+        .push(if value == 10 { "present" } else { "early" });
+}
+
+fn reset(w: &mut World) {
+    w.replace(0u32);
+}
+
+fn first(w: &World) {
+    w.get_mut::<Log>().unwrap().0.push("first");
+}
+
+fn second(w: &World) {
+    w.get_mut::<Log>().unwrap().0.push("second");
+}
+
+fn slow_insert(w: &World) {
+    std::thread::sleep(Duration::from_millis(2));
+    w.commands().insert(1u8);
+}
+
+fn fast_insert(w: &World) {
+    w.commands().insert(2u8);
+}
+
+#[test]
+fn main() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(add).unwrap().writes::<u32>();
+    registry
+        .insert(multiply)
+        .unwrap()
+        .after(add)
+        .writes::<u32>();
+    registry.insert(count).unwrap().writes::<u64>();
+    registry
+        .insert(present)
+        .unwrap()
+        .main_thread()
+        .after(multiply)
+        .reads::<u32>()
+        .writes::<Log>();
+    registry.insert_exclusive(reset).unwrap().after(present);
+
+    let mut world = World::default();
+    world.insert(0u32);
+    world.insert(0u64);
+    world.insert(Log(Vec::new()));
+
+    let mut builder = registry.sort().unwrap();
+    builder.executor(Executor::WorkStealing);
+    builder.profile(4);
+    let mut dispatcher = builder.build(Arc::new(world), Some(3));
+    for _ in 0..5 {
+        dispatcher.dispatch().unwrap();
+    }
+
+    let world = dispatcher.world();
+    assert_eq!(*world.get::<u32>().unwrap(), 0);
+    assert_eq!(*world.get::<u64>().unwrap(), 5);
+    assert_eq!(world.get::<Log>().unwrap().0, vec!["present"; 5]);
+    assert_eq!(
+        dispatcher
+            .stats()
+            .unwrap()
+            .system(StageId::of(&add))
+            .unwrap()
+            .samples,
+        4
+    );
+
+    // Schedule changes keep working with the work-stealing executor
+    dispatcher.set_enabled(StageId::of(&count), false);
+    dispatcher.remove(StageId::of(&reset));
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*dispatcher.world().get::<u32>().unwrap(), 10);
+    assert_eq!(*dispatcher.world().get::<u64>().unwrap(), 5);
+
+    dispatcher
+        .rebuild(|registry| {
+            registry.insert_exclusive(reset).unwrap().after(present);
+        })
+        .unwrap();
+    dispatcher.dispatch().unwrap();
+    assert_eq!(*dispatcher.world().get::<u32>().unwrap(), 0);
+}
+
+#[test]
+fn conflicts() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(first).unwrap().writes::<Log>();
+    registry.insert(second).unwrap().writes::<Log>();
+
+    // Conflicting systems keep executing in the order of their groups, even without any rules between them
+    let mut builder = registry.sort().unwrap();
+    let expected = (0..2)
+        .map(|x| builder.group(x).unwrap()[0].name)
+        .map(|x| x.rsplit("::").next().unwrap())
+        .collect::<Vec<_>>();
+    builder.executor(Executor::WorkStealing);
+
+    let mut world = World::default();
+    world.insert(Log(Vec::new()));
+    let mut dispatcher = builder.build(Arc::new(world), Some(4));
+    for _ in 0..10 {
+        dispatcher.dispatch().unwrap();
+    }
+
+    let log = dispatcher.world().get::<Log>().unwrap().0.clone();
+    assert_eq!(log, expected.repeat(10));
+}
+
+#[test]
+fn commands() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry
+        .insert(slow_insert)
+        .unwrap()
+        .cost(Duration::from_millis(2));
+    registry
+        .insert(fast_insert)
+        .unwrap()
+        .cost(Duration::from_millis(1));
+
+    // Both systems are packed onto the same thread, but the main thread steals the fast one while the slow one executes
+    // Their commands still get applied in the order of the schedule, even though the fast one finishes first
+    let mut builder = registry.sort().unwrap();
+    builder.executor(Executor::WorkStealing);
+    builder.balance(Some(1));
+    let stages = builder.stages_at(0, 0).unwrap();
+    assert_eq!(
+        stages,
+        vec![StageId::of(&slow_insert), StageId::of(&fast_insert)]
+    );
+
+    let mut dispatcher = builder.build(Arc::new(World::default()), None);
+    for _ in 0..10 {
+        dispatcher.dispatch().unwrap();
+        assert_eq!(*dispatcher.world().get::<u8>().unwrap(), 2);
+    }
+}
+
+#[test]
+fn idle() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(slow_insert).unwrap();
+
+    let mut builder = registry.sort().unwrap();
+    builder.executor(Executor::WorkStealing);
+    builder.profile(4);
+    let mut dispatcher = builder.build(Arc::new(World::default()), Some(2));
+    for _ in 0..4 {
+        dispatcher.dispatch().unwrap();
+    }
+
+    // Only one thread executes the slow system, the other ones sleep until it finishes
+    let stats = dispatcher.stats().unwrap();
+    let idle = (0..3)
+        .map(|thread| stats.idle(thread, 0).unwrap())
+        .collect::<Vec<_>>();
+    assert!(idle.iter().all(|x| x.samples == 4));
+    assert!(idle.iter().map(|x| x.avg).sum::<Duration>() >= Duration::from_millis(2));
+}
@@ -0,0 +1,43 @@
+#![allow(unused_must_use)]
+use dispatcher_system::*;
+use std::sync::Arc;
+
+fn system_a(_: &World) {}
+fn system_b(_: &World) {}
+
+#[test]
+fn chrome() {
+    env_logger::Builder::from_default_env()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+
+    let mut registry = Registry::default();
+    registry.insert(system_a).unwrap();
+    registry.insert(system_b).unwrap().after(system_a);
+
+    let mut dispatcher = registry
+        .sort()
+        .unwrap()
+        .build(Arc::new(World::default()), None);
+    assert!(dispatcher.trace().is_none());
+
+    dispatcher.record_trace(2);
+    for _ in 0..3 {
+        dispatcher.dispatch().unwrap();
+    }
+
+    let json = dispatcher.trace().unwrap();
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.contains(r#""args":{"name":"thread-0"}"#));
+    assert!(json.contains(r#""args":{"name":"main"}"#));
+    assert_eq!(json.matches(r#""cat":"frame""#).count(), 2);
+    assert_eq!(json.matches(StageId::of(&system_a).name).count(), 2);
+    assert_eq!(json.matches(StageId::of(&system_b).name).count(), 2);
+    assert!(json.contains(r#""name":"barrier (group 1)","cat":"barrier""#));
+
+    let path = std::env::temp_dir().join("dispatcher-system-trace.json");
+    dispatcher.write_trace(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), json);
+    std::fs::remove_file(path).unwrap();
+}